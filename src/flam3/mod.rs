@@ -0,0 +1,461 @@
+//! Conversion of flam3 XML flames, as written by flam3, Apophysis and
+//! Chaotica, into systems that can be written as `chaoskit.capnp` messages.
+
+mod xml;
+
+use std::f64;
+use interop::Error;
+use types::{Number, Point, System, Transform, AffineTransformation, RenderSettings};
+use types::system::SystemBuilder;
+use types::transform::TransformBuilder;
+use types::coloring_method::{Noop, SingleColor};
+use types::render_settings::{DensityEstimation, PaletteSource, PaletteStop};
+use variations;
+use self::xml::Element;
+
+/// flam3 variations that have a counterpart in `variations`, with the
+/// attributes holding their parameters and the names of those parameters.
+const VARIATIONS: &'static [(&'static str, &'static str, &'static [(&'static str, &'static str)])] = &[
+    ("linear", "Linear", &[]),
+    ("sinusoidal", "Sinusoidal", &[]),
+    ("spherical", "Spherical", &[]),
+    ("swirl", "Swirl", &[]),
+    ("horseshoe", "Horseshoe", &[]),
+    ("polar", "Polar", &[]),
+    ("handkerchief", "Handkerchief", &[]),
+    ("heart", "Heart", &[]),
+    ("disc", "Disc", &[]),
+    ("spiral", "Spiral", &[]),
+    ("hyperbolic", "Hyperbolic", &[]),
+    ("diamond", "Diamond", &[]),
+    ("ex", "Ex", &[]),
+    ("julia", "Julia", &[]),
+    ("bent", "Bent", &[]),
+    ("waves", "Waves", &[]),
+    ("fisheye", "Fisheye", &[]),
+    ("popcorn", "Popcorn", &[]),
+    ("exponential", "Exponential", &[]),
+    ("power", "Power", &[]),
+    ("cosine", "Cosine", &[]),
+    ("rings", "Rings", &[]),
+    ("fan", "Fan", &[]),
+    ("blob", "Blob", &[("blob_high", "high"), ("blob_low", "low"), ("blob_waves", "waves")]),
+    ("pdj", "Pdj", &[("pdj_a", "a"), ("pdj_b", "b"), ("pdj_c", "c"), ("pdj_d", "d")]),
+    ("fan2", "Fan2", &[("fan2_x", "x"), ("fan2_y", "y")]),
+    ("rings2", "Rings2", &[("rings2_val", "value")]),
+    ("eyefish", "Eyefish", &[]),
+    ("bubble", "Bubble", &[]),
+    ("cylinder", "Cylinder", &[]),
+    ("perspective", "Perspective", &[("perspective_angle", "angle"), ("perspective_dist", "distance")]),
+    ("noise", "Noise", &[]),
+    ("julian", "Julian", &[("julian_power", "power"), ("julian_dist", "distance")]),
+    ("juliascope", "Juliascope", &[("juliascope_power", "power"), ("juliascope_dist", "distance")]),
+    ("blur", "Blur", &[]),
+    ("gaussian_blur", "GaussianBlur", &[]),
+    ("pie", "Pie", &[("pie_slices", "slices"), ("pie_rotation", "rotation"), ("pie_thickness", "thickness")]),
+    ("ngon", "Ngon", &[("ngon_sides", "sides"), ("ngon_power", "power"), ("ngon_circle", "circle"), ("ngon_corners", "corners")]),
+    ("curl", "Curl", &[("curl_c1", "c1"), ("curl_c2", "c2")]),
+    ("rectangles", "Rectangles", &[("rectangles_x", "x"), ("rectangles_y", "y")]),
+    ("tangent", "Tangent", &[]),
+    ("square", "Square", &[]),
+    ("cross", "Cross", &[]),
+    ("disc2", "Disc2", &[("disc2_rot", "rotation"), ("disc2_twist", "twist")]),
+    ("super_shape", "SuperShape", &[("super_shape_rnd", "rnd"), ("super_shape_m", "m"), ("super_shape_n1", "n1"), ("super_shape_n2", "n2"), ("super_shape_n3", "n3"), ("super_shape_holes", "holes")]),
+    ("flower", "Flower", &[("flower_petals", "petals"), ("flower_holes", "holes")]),
+    ("conic", "Conic", &[("conic_eccentricity", "eccentricity"), ("conic_holes", "holes")]),
+    ("parabola", "Parabola", &[("parabola_height", "height"), ("parabola_width", "width")]),
+    ("bent2", "Bent2", &[("bent2_x", "x"), ("bent2_y", "y")]),
+    ("bipolar", "Bipolar", &[("bipolar_shift", "shift")]),
+    ("boarders", "Boarders", &[]),
+    ("butterfly", "Butterfly", &[]),
+    ("cell", "Cell", &[("cell_size", "size")]),
+    ("cpow", "Cpow", &[("cpow_r", "real"), ("cpow_i", "imaginary"), ("cpow_power", "power")]),
+    ("curve", "Curve", &[("curve_xamp", "x_amplitude"), ("curve_yamp", "y_amplitude"), ("curve_xlength", "x_length"), ("curve_ylength", "y_length")]),
+    ("edisc", "Edisc", &[]),
+    ("elliptic", "Elliptic", &[]),
+    ("escher", "Escher", &[("escher_beta", "beta")]),
+    ("foci", "Foci", &[]),
+    ("modulus", "Modulus", &[("modulus_x", "x"), ("modulus_y", "y")]),
+    ("oscilloscope", "Oscilloscope", &[("oscilloscope_separation", "separation"), ("oscilloscope_frequency", "frequency"), ("oscilloscope_amplitude", "amplitude"), ("oscilloscope_damping", "damping")]),
+    ("polar2", "Polar2", &[]),
+    ("popcorn2", "Popcorn2", &[("popcorn2_x", "x"), ("popcorn2_y", "y"), ("popcorn2_c", "c")]),
+    ("separation", "Separation", &[("separation_x", "x"), ("separation_xinside", "x_inside"), ("separation_y", "y"), ("separation_yinside", "y_inside")]),
+    ("split", "Split", &[("split_xsize", "x_size"), ("split_ysize", "y_size")]),
+    ("splits", "Splits", &[("splits_x", "x"), ("splits_y", "y")]),
+    ("stripes", "Stripes", &[("stripes_space", "space"), ("stripes_warp", "warp")]),
+    ("wedge", "Wedge", &[("wedge_angle", "angle"), ("wedge_hole", "hole"), ("wedge_count", "count"), ("wedge_swirl", "swirl")]),
+    ("wedge_julia", "WedgeJulia", &[("wedge_julia_angle", "angle"), ("wedge_julia_count", "count"), ("wedge_julia_power", "power"), ("wedge_julia_dist", "distance")]),
+    ("wedge_sph", "WedgeSph", &[("wedge_sph_angle", "angle"), ("wedge_sph_count", "count"), ("wedge_sph_hole", "hole"), ("wedge_sph_swirl", "swirl")]),
+    ("waves2", "Waves2", &[("waves2_freqx", "x_frequency"), ("waves2_scalex", "x_scale"), ("waves2_freqy", "y_frequency"), ("waves2_scaley", "y_scale")]),
+    ("exp", "Exp", &[]),
+    ("log", "Log", &[]),
+    ("sin", "Sin", &[]),
+    ("cos", "Cos", &[]),
+    ("tan", "Tan", &[]),
+    ("sec", "Sec", &[]),
+    ("csc", "Csc", &[]),
+    ("cot", "Cot", &[]),
+    ("sinh", "Sinh", &[]),
+    ("cosh", "Cosh", &[]),
+    ("tanh", "Tanh", &[]),
+    ("sech", "Sech", &[]),
+    ("csch", "Csch", &[]),
+    ("coth", "Coth", &[]),
+    ("auger", "Auger", &[("auger_freq", "frequency"), ("auger_weight", "amount"), ("auger_scale", "scale"), ("auger_sym", "symmetry")]),
+    ("mobius", "Mobius", &[("re_a", "re_a"), ("im_a", "im_a"), ("re_b", "re_b"), ("im_b", "im_b"), ("re_c", "re_c"), ("im_c", "im_c"), ("re_d", "re_d"), ("im_d", "im_d")])
+];
+
+/// Attributes of `<xform>` that aren't variations.
+const XFORM_ATTRIBUTES: &'static [&'static str] = &[
+    "weight", "color", "symmetry", "color_speed", "coefs", "post", "opacity", "animate", "chaos",
+    "name", "var_color", "plotmode", "motion_frequency", "motion_function", "var", "var1"
+];
+
+// Particles get reset every this many iterations, flam3 has no equivalent
+const DEFAULT_TTL: i32 = 30;
+
+pub struct Conversion {
+    pub name: String,
+    /// Flames are converted independently, so one that can't be doesn't
+    /// stop the others.
+    pub system: Result<System, Error>,
+    /// Names of the xform attributes that were dropped because they're
+    /// neither supported variations nor their parameters, mostly unsupported
+    /// variations and their parameters.
+    pub unsupported: Vec<String>
+}
+
+/// Converts every `<flame>` found in the document into a system, only
+/// failing as a whole if the document can't be read.
+pub fn convert(input: &str) -> Result<Vec<Conversion>, Error> {
+    let root = try!(xml::parse(input));
+
+    let flames = if root.name == "flame" { vec![&root] } else { root.children_named("flame") };
+    if flames.is_empty() {
+        return Err(Error::new("No flames found"));
+    }
+
+    Ok(flames.into_iter().map(convert_flame).collect())
+}
+
+fn convert_flame(element: &Element) -> Conversion {
+    let mut unsupported = Vec::new();
+    let system = convert_system(element, &mut unsupported);
+
+    Conversion {
+        name: element.attribute("name").unwrap_or("").to_string(),
+        system: system,
+        unsupported: unsupported
+    }
+}
+
+fn convert_system(element: &Element, unsupported: &mut Vec<String>) -> Result<System, Error> {
+    let mut builder = SystemBuilder::new();
+
+    for (i, xform) in element.children_named("xform").into_iter().enumerate() {
+        let weight = try!(number_attribute(xform, "weight", 1.0));
+        let transform = try!(convert_xform(xform, unsupported));
+
+        builder = builder.add_weighted_transform(transform, weight);
+
+        // flam3 calls xaos "chaos", one value per xform
+        if let Some(chaos) = xform.attribute("chaos") {
+            builder = builder.xaos(i, try!(parse_numbers(chaos)));
+        }
+    }
+
+    if let Some(final_xform) = element.child("finalxform") {
+        builder = builder.final_transform(try!(convert_xform(final_xform, unsupported)));
+    }
+
+    Ok(try!(builder
+        .ttl(DEFAULT_TTL)
+        .render_settings(try!(convert_render_settings(element)))
+        .finalize()))
+}
+
+fn convert_xform(xform: &Element, unsupported: &mut Vec<String>) -> Result<Transform, Error> {
+    let mut builder = TransformBuilder::new();
+
+    if let Some(coefs) = xform.attribute("coefs") {
+        builder = builder.pre(try!(convert_coefs(coefs)));
+    }
+
+    if let Some(coefs) = xform.attribute("post") {
+        builder = builder.post(try!(convert_coefs(coefs)));
+    }
+
+    let color = try!(number_attribute(xform, "color", 0.0));
+    let color_speed = match xform.attribute("color_speed") {
+        Some(_) => try!(number_attribute(xform, "color_speed", 0.5)),
+        None => (1.0 - try!(number_attribute(xform, "symmetry", 0.0))) / 2.0
+    };
+
+    if !(color >= 0.0 && color <= 1.0 && color_speed >= 0.0 && color_speed <= 1.0) {
+        return Err(Error::new("Xform color and color speed must be between 0 and 1"));
+    }
+
+    builder = if color_speed == 0.0 {
+        builder.coloring_method(Box::new(Noop))
+    } else {
+        builder.coloring_method(Box::new(SingleColor::with_speed(color, color_speed)))
+    };
+
+    for &(ref name, ref value) in xform.attributes.iter() {
+        if XFORM_ATTRIBUTES.contains(&&name[..]) || is_parameter(name) {
+            continue;
+        }
+
+        match VARIATIONS.iter().find(|&&(flam3_name, _, _)| flam3_name == name) {
+            Some(&(_, variation_name, param_attributes)) => {
+                let weight = try!(parse_number(value));
+                if weight == 0.0 {
+                    continue;
+                }
+
+                // Parameters left out of the xform take the variation's defaults
+                let mut params = Vec::with_capacity(param_attributes.len());
+                for &(attribute, param) in param_attributes.iter() {
+                    if let Some(value) = xform.attribute(attribute) {
+                        params.push((param, try!(parse_number(value))));
+                    }
+                }
+
+                let variation = try!(variations::make_variation_with_named_params(variation_name, &[], &params));
+                builder = builder.add_boxed_2d_weighted_variation(variation, weight, weight);
+            },
+            // Unsupported variations that are switched off don't matter
+            None => {
+                if parse_number(value).ok() != Some(0.0) && !unsupported.contains(name) {
+                    unsupported.push(name.clone());
+                }
+            }
+        }
+    }
+
+    Ok(builder.finalize())
+}
+
+/// Whether the attribute holds a parameter of a supported variation, like
+/// `julian_power`.
+fn is_parameter(name: &str) -> bool {
+    VARIATIONS.iter().any(|&(_, _, params)| params.iter().any(|&(attribute, _)| attribute == name))
+}
+
+// flam3 maps (x, y) to (ax + cy + e, bx + dy + f)
+fn convert_coefs(coefs: &str) -> Result<AffineTransformation, Error> {
+    let values = try!(parse_numbers(coefs));
+    if values.len() != 6 {
+        return Err(Error::new("Affine coefficients must have 6 values"));
+    }
+
+    Ok(AffineTransformation::from_coefficients(values[0], values[2], values[4], values[1], values[3], values[5]))
+}
+
+fn convert_render_settings(element: &Element) -> Result<RenderSettings, Error> {
+    let mut settings = RenderSettings::new();
+
+    if let Some(size) = element.attribute("size") {
+        let values = try!(parse_numbers(size));
+        if values.len() != 2 {
+            return Err(Error::new("Flame size must have 2 values"));
+        }
+
+        settings.width = values[0] as usize;
+        settings.height = values[1] as usize;
+    }
+
+    if let Some(center) = element.attribute("center") {
+        let values = try!(parse_numbers(center));
+        if values.len() != 2 {
+            return Err(Error::new("Flame center must have 2 values"));
+        }
+
+        settings.camera.center = Point::from_xy(values[0], values[1]);
+    }
+
+    // flam3's scale is in pixels per unit
+    let shorter_side = settings.width.min(settings.height) as Number;
+    let scale = try!(number_attribute(element, "scale", shorter_side / 4.0)) * (2.0 as Number).powf(try!(number_attribute(element, "zoom", 0.0)));
+    settings.camera.zoom = scale * 4.0 / shorter_side;
+    settings.camera.rotation = try!(number_attribute(element, "rotate", 0.0)) * f64::consts::PI / 180.0;
+
+    if element.attribute("gamma").is_some() {
+        settings.gamma = Some(try!(number_attribute(element, "gamma", 4.0)));
+    }
+
+    if let Some(background) = element.attribute("background") {
+        let values = try!(parse_numbers(background));
+        if values.len() != 3 {
+            return Err(Error::new("Flame background must have 3 values"));
+        }
+
+        settings.background = (values[0], values[1], values[2]);
+    }
+
+    // flam3 does density estimation unless the radius is 0
+    let defaults = DensityEstimation::new();
+    let estimation = DensityEstimation {
+        max_radius: try!(number_attribute(element, "estimator_radius", defaults.max_radius)),
+        min_radius: try!(number_attribute(element, "estimator_minimum", defaults.min_radius)),
+        curve: try!(number_attribute(element, "estimator_curve", defaults.curve))
+    };
+
+    if estimation.max_radius > 0.0 {
+        settings.density_estimation = Some(estimation);
+    }
+
+    let stops = try!(read_palette(element));
+    if !stops.is_empty() {
+        settings.palette = Some(PaletteSource::Stops(stops));
+    }
+
+    Ok(settings)
+}
+
+/// Reads the palette either from `<color index="…" rgb="…"/>` elements or a
+/// hex-encoded `<palette>` element.
+fn read_palette(element: &Element) -> Result<Vec<PaletteStop>, Error> {
+    let mut stops = Vec::new();
+
+    for color in element.children_named("color") {
+        let index = try!(number_attribute(color, "index", 0.0));
+        let rgb = try!(parse_numbers(try!(color.attribute("rgb").ok_or(Error::new("Palette color without rgb")))));
+        if rgb.len() != 3 {
+            return Err(Error::new("Palette colors must have 3 values"));
+        }
+
+        stops.push(PaletteStop {
+            position: index / 255.0,
+            color: (rgb[0] / 255.0, rgb[1] / 255.0, rgb[2] / 255.0, 1.0)
+        });
+    }
+
+    if let Some(palette) = element.child("palette") {
+        let hex: Vec<u8> = palette.text.bytes().filter(|byte| !(*byte as char).is_whitespace()).collect();
+        let channels = if palette.attribute("format") == Some("RGBA") { 4 } else { 3 };
+        let count = hex.len() / (channels * 2);
+
+        for i in 0..count {
+            let mut rgb = [0.0; 3];
+            for channel in 0..3 {
+                let offset = (i * channels + channel) * 2;
+                rgb[channel] = try!(parse_hex_byte(&hex[offset..offset + 2])) / 255.0;
+            }
+
+            let position = if count > 1 { (i as Number) / ((count - 1) as Number) } else { 0.0 };
+            stops.push(PaletteStop { position: position, color: (rgb[0], rgb[1], rgb[2], 1.0) });
+        }
+    }
+
+    Ok(stops)
+}
+
+fn parse_hex_byte(digits: &[u8]) -> Result<Number, Error> {
+    let mut value = 0;
+    for &digit in digits.iter() {
+        value = value * 16 + try!((digit as char).to_digit(16).ok_or(Error::new("Invalid hex digit in palette")));
+    }
+
+    Ok(value as Number)
+}
+
+fn number_attribute(element: &Element, name: &str, default: Number) -> Result<Number, Error> {
+    match element.attribute(name) {
+        Some(value) => parse_number(value),
+        None => Ok(default)
+    }
+}
+
+fn parse_number(value: &str) -> Result<Number, Error> {
+    value.trim().parse::<Number>().map_err(|_| Error::new("Invalid number in flame attribute"))
+}
+
+fn parse_numbers(values: &str) -> Result<Vec<Number>, Error> {
+    let mut numbers = Vec::new();
+    for value in values.split_whitespace() {
+        numbers.push(try!(parse_number(value)));
+    }
+
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::convert;
+    use types::System;
+
+    fn variation_names(system: &System, transform: usize) -> Vec<String> {
+        let (transform, _) = system.transforms()[transform];
+        transform.variations().iter().map(|&(ref variation, _, _)| variation.name().to_string()).collect()
+    }
+
+    #[test]
+    fn converts_xforms_and_render_settings() {
+        let conversions = convert(r#"
+            <flame name="test" size="200 100" center="1 -1" scale="50" gamma="3" estimator_radius="0">
+                <xform weight="2" color="0.25" color_speed="0.1" coefs="1 0 0 1 0.5 0" chaos="0 1"
+                    julian="0.5" julian_power="3" julian_dist="1" wedge="1" wedge_count="2"
+                    wedge_julia="1" wedge_julia_power="2" arch="1" whorl="0" whorl_inside="0.5"/>
+                <xform weight="1" color="1" symmetry="1" linear="1"/>
+                <finalxform spherical="1"/>
+                <color index="0" rgb="255 0 0"/>
+                <color index="255" rgb="0 0 255"/>
+            </flame>"#).unwrap();
+
+        assert_eq!(conversions.len(), 1);
+        assert_eq!(conversions[0].name, "test");
+        assert_eq!(conversions[0].unsupported, vec!["arch".to_string(), "whorl_inside".to_string()]);
+
+        let system = conversions[0].system.as_ref().unwrap();
+        let transforms = system.transforms();
+        assert_eq!(transforms.len(), 2);
+        assert_eq!(transforms[0].1, 2.0);
+        assert_eq!(variation_names(system, 0), vec!["Julian", "Wedge", "WedgeJulia"]);
+        assert_eq!(transforms[0].0.variations()[0].0.params(), vec![3.0, 1.0]);
+        assert_eq!(transforms[0].0.coloring_method().params(), vec![0.25, 0.1]);
+        assert_eq!(transforms[0].0.pre().coefficients(), [1.0, 0.0, 0.5, 0.0, 1.0, 0.0]);
+        assert_eq!(system.xaos()[0], &[0.0, 1.0][..]);
+
+        // Fully symmetric xforms don't change the color
+        assert_eq!(transforms[1].0.coloring_method().name(), "noop");
+        assert_eq!(variation_names(system, 1), vec!["Linear"]);
+
+        let settings = &system.render_settings;
+        assert_eq!((settings.width, settings.height), (200, 100));
+        assert_eq!((settings.camera.center.x, settings.camera.center.y), (1.0, -1.0));
+        assert_eq!(settings.camera.zoom, 2.0);
+        assert_eq!(settings.gamma, Some(3.0));
+        assert!(settings.density_estimation.is_none());
+        assert!(settings.palette.is_some());
+    }
+
+    #[test]
+    fn bad_flames_dont_stop_the_others() {
+        let conversions = convert(r#"
+            <flames>
+                <flame name="good"><xform weight="1" linear="1"/></flame>
+                <flame name="bad parameter"><xform weight="1" julian="1" julian_power="0"/></flame>
+                <flame name="bad number"><xform weight="x" linear="1"/></flame>
+                <flame name="bad color speed"><xform weight="1" color_speed="2" linear="1"/></flame>
+                <flame name="no weight"><xform weight="0" linear="1"/></flame>
+                <flame name="also good"><xform weight="1" spherical="1"/></flame>
+            </flames>"#).unwrap();
+
+        let results: Vec<(&str, bool)> = conversions.iter().map(|conversion| (&conversion.name[..], conversion.system.is_ok())).collect();
+        assert_eq!(results, vec![
+            ("good", true),
+            ("bad parameter", false),
+            ("bad number", false),
+            ("bad color speed", false),
+            ("no weight", false),
+            ("also good", true)
+        ]);
+    }
+
+    #[test]
+    fn unreadable_documents_are_errors() {
+        assert!(convert("<flames>").is_err());
+        assert!(convert("<flames></flames>").is_err());
+    }
+}
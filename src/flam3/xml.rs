@@ -0,0 +1,212 @@
+/// Just enough XML to read flam3 files: elements, attributes and text.
+/// Comments, processing instructions and doctypes are skipped.
+#[derive(Debug)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|&&(ref key, _)| key == name).map(|&(_, ref value)| &value[..])
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named(&self, name: &str) -> Vec<&Element> {
+        self.children.iter().filter(|child| child.name == name).collect()
+    }
+}
+
+pub fn parse(input: &str) -> Result<Element, &'static str> {
+    let mut parser = Parser { input: input, position: 0 };
+
+    try!(parser.skip_prolog());
+    parser.parse_element()
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_left();
+        self.position = self.input.len() - trimmed.len();
+    }
+
+    fn skip_past(&mut self, pattern: &str) -> Result<(), &'static str> {
+        match self.rest().find(pattern) {
+            Some(index) => {
+                self.position += index + pattern.len();
+                Ok(())
+            },
+            None => Err("Unexpected end of XML")
+        }
+    }
+
+    fn skip_prolog(&mut self) -> Result<(), &'static str> {
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with("<?") {
+                try!(self.skip_past("?>"));
+            } else if self.rest().starts_with("<!--") {
+                try!(self.skip_past("-->"));
+            } else if self.rest().starts_with("<!") {
+                try!(self.skip_past(">"));
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<String, &'static str> {
+        let rest = self.rest();
+        let length = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(rest.len());
+
+        if length == 0 {
+            return Err("Expected a name in XML");
+        }
+
+        self.position += length;
+        Ok(rest[..length].to_string())
+    }
+
+    fn parse_element(&mut self) -> Result<Element, &'static str> {
+        if !self.rest().starts_with("<") {
+            return Err("Expected an XML element");
+        }
+        self.position += 1;
+
+        let mut element = Element {
+            name: try!(self.parse_name()),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new()
+        };
+
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            } else if self.rest().starts_with(">") {
+                self.position += 1;
+                break;
+            }
+
+            let name = try!(self.parse_name());
+
+            self.skip_whitespace();
+            if !self.rest().starts_with("=") {
+                return Err("Expected = after an XML attribute name");
+            }
+            self.position += 1;
+            self.skip_whitespace();
+
+            let quote = match self.rest().chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => quote,
+                _ => return Err("Expected a quoted XML attribute value")
+            };
+            self.position += 1;
+
+            let length = try!(self.rest().find(quote).ok_or("Unterminated XML attribute value"));
+            let value = unescape(&self.rest()[..length]);
+            self.position += length + 1;
+
+            element.attributes.push((name, value));
+        }
+
+        loop {
+            let length = try!(self.rest().find('<').ok_or("Unexpected end of XML"));
+            element.text.push_str(&unescape(&self.rest()[..length]));
+            self.position += length;
+
+            if self.rest().starts_with("</") {
+                self.position += 2;
+                if try!(self.parse_name()) != element.name {
+                    return Err("Mismatched XML closing tag");
+                }
+
+                self.skip_whitespace();
+                if !self.rest().starts_with(">") {
+                    return Err("Expected > after an XML closing tag name");
+                }
+                self.position += 1;
+
+                return Ok(element);
+            } else if self.rest().starts_with("<!--") {
+                try!(self.skip_past("-->"));
+            } else if self.rest().starts_with("<![CDATA[") {
+                self.position += 9;
+                let length = try!(self.rest().find("]]>").ok_or("Unterminated CDATA section"));
+                element.text.push_str(&self.rest()[..length]);
+                self.position += length + 3;
+            } else if self.rest().starts_with("<?") {
+                try!(self.skip_past("?>"));
+            } else {
+                element.children.push(try!(self.parse_element()));
+            }
+        }
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn parses_elements_attributes_and_text() {
+        let root = parse(r#"<?xml version="1.0"?>
+            <!-- flames -->
+            <flames name='a &amp; b'>
+                <flame size="1 2"/>
+                <palette count="1">FF<![CDATA[00]]>&lt;</palette>
+            </flames>"#).unwrap();
+
+        assert_eq!(root.name, "flames");
+        assert_eq!(root.attribute("name"), Some("a & b"));
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.child("flame").and_then(|flame| flame.attribute("size")), Some("1 2"));
+        assert_eq!(root.child("palette").map(|palette| &palette.text[..]), Some("FF00<"));
+        assert_eq!(root.children_named("flame").len(), 1);
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        let documents = [
+            "",
+            "flames",
+            "<flames>",
+            "<flames></flame>",
+            "<flames><flame></flames></flame>",
+            "<flames></flames",
+            "<flame size=1/>",
+            "<flame size/>",
+            "<flame size=\"1/>"
+        ];
+
+        for document in documents.iter() {
+            assert!(parse(document).is_err(), "{}", document);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use types::Particle;
+use types::render_settings::Camera;
+use render::{Color, ImagePalette, Palette};
+use byteorder::{ByteOrder, BigEndian};
+use std::io::{self, Write};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Bin {
+    pub density: f64,
+    pub color: Color
+}
+
+/// Particles binned into image pixels, so only the accumulated buffer has to
+/// be sent to the client.
+///
+/// Every bin keeps the number of particles that landed in it and the sum of
+/// their colors looked up in the palette, the same way the client
+/// accumulates particles.
+pub struct Histogram<'a> {
+    width: usize,
+    height: usize,
+    camera: Camera,
+    palette: &'a ImagePalette,
+    bins: Vec<Bin>,
+    samples: u64
+}
+
+impl<'a> Histogram<'a> {
+    pub fn new(width: usize, height: usize, camera: Camera, palette: &'a ImagePalette) -> Histogram<'a> {
+        Histogram {
+            width: width,
+            height: height,
+            camera: camera,
+            palette: palette,
+            bins: vec![Bin { density: 0.0, color: Color::new() }; width * height],
+            samples: 0
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    pub fn add(&mut self, particle: &Particle) {
+        let projected = self.camera.project(&particle.point, self.width, self.height);
+        let (x, y) = (projected.x, projected.y);
+
+        if x >= 0.0 && y >= 0.0 && x < (self.width as f64) && y < (self.height as f64) {
+            let bin = &mut self.bins[(y.trunc() as usize) * self.width + (x.trunc() as usize)];
+
+            bin.density += 1.0;
+            bin.color = &bin.color + &self.palette.color_at(particle.color);
+        }
+
+        self.samples += 1;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (bin, other_bin) in self.bins.iter_mut().zip(other.bins.iter()) {
+            bin.density += other_bin.density;
+            bin.color = &bin.color + &other_bin.color;
+        }
+
+        self.samples += other.samples;
+    }
+
+    /// Writes the histogram as a frame: the width and height as big-endian
+    /// `u32`s followed by the density and the red, green, blue and alpha sums
+    /// of every bin as big-endian `f64`s, row by row.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut header: [u8; 4*2] = [0; 4 * 2];
+        BigEndian::write_u32(&mut header[4*0..4*1], self.width as u32);
+        BigEndian::write_u32(&mut header[4*1..4*2], self.height as u32);
+        try!(writer.write_all(&header));
+
+        let mut buffer: [u8; 8*5] = [0; 8 * 5];
+        for bin in self.bins.iter() {
+            BigEndian::write_f64(&mut buffer[8*0..8*1], bin.density);
+            BigEndian::write_f64(&mut buffer[8*1..8*2], bin.color.r);
+            BigEndian::write_f64(&mut buffer[8*2..8*3], bin.color.g);
+            BigEndian::write_f64(&mut buffer[8*3..8*4], bin.color.b);
+            BigEndian::write_f64(&mut buffer[8*4..8*5], bin.color.a);
+            try!(writer.write_all(&buffer));
+        }
+
+        writer.flush()
+    }
+}
@@ -1,21 +1,22 @@
 use capnp;
 use std::fmt;
+use std::io;
 use std::error::Error as StdError;
 
 #[derive(Debug)]
 pub struct Error {
-    description: &'static str
+    description: String
 }
 
 impl Error {
     pub fn new(description: &'static str) -> Error {
-        Error { description: description }
+        Error { description: description.to_string() }
     }
 }
 
 impl StdError for Error {
     fn description(&self) -> &str {
-        self.description
+        &self.description
     }
 
     fn cause(&self) -> Option<&StdError> {
@@ -26,14 +27,26 @@ impl StdError for Error {
 impl From<capnp::Error> for Error {
     fn from(err: capnp::Error) -> Error {
         match err {
-            capnp::Error::Decode { description: d, detail: _ } => Error { description: d },
-            capnp::Error::Io(_) => Error { description: "IO Error" }
+            capnp::Error::Decode { description: d, detail: _ } => Error::new(d),
+            capnp::Error::Io(err) => Error::from(err)
         }
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error { description: format!("IO Error: {}", err) }
+    }
+}
+
 impl From<&'static str> for Error {
     fn from(err: &'static str) -> Error {
+        Error::new(err)
+    }
+}
+
+impl From<String> for Error {
+    fn from(err: String) -> Error {
         Error { description: err }
     }
 }
@@ -0,0 +1,898 @@
+//! JSON representation of messages, mirroring `chaoskit.capnp` field by field
+//! so flames can be kept in version control and edited by hand.
+//!
+//! Struct fields use the same camel case names as the schema and can be left
+//! out to get their defaults, while unknown ones are rejected. Unions are objects with a single field named
+//! after the variant, or just the variant name as a string for `Void` ones,
+//! e.g. `"coloringMethod": {"singleColor": 0.5}` or `"gamma": "automatic"`.
+//! A variation's `namedParams` are an object mapping parameter names to
+//! values, and embedded palette images are base64 encoded.
+
+use std::{i32, u32};
+use std::io::{BufRead, Read, Write};
+use rustc_serialize::json::{Json, Object, Array};
+use rustc_serialize::base64::{ToBase64, FromBase64, STANDARD};
+use types::Message;
+use variations;
+use variations::VariationRegistry;
+use interop::{Error, read_message_root};
+
+use chaoskit_capnp::{message, MessageType, flame, transform, variation, affine_transformation, extent};
+use chaoskit_capnp::{render_settings, camera, color, palette_stop, density_estimation};
+use capnp;
+use capnp::serialize;
+
+/// Reads the next JSON message, returning `None` once the input ends.
+/// Variations are created with the global registry.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Message>, Error> {
+    match try!(read_value(reader)) {
+        Some(json) => {
+            let registry = variations::global_registry().read().unwrap();
+            convert_message(&json, &registry).map(Some)
+        },
+        None => Ok(None)
+    }
+}
+
+pub fn read_message_with_registry<R: BufRead>(reader: &mut R, registry: &VariationRegistry) -> Result<Option<Message>, Error> {
+    match try!(read_value(reader)) {
+        Some(json) => convert_message(&json, registry).map(Some),
+        None => Ok(None)
+    }
+}
+
+fn convert_message(json: &Json, registry: &VariationRegistry) -> Result<Message, Error> {
+    let mut message_builder = capnp::message::Builder::new_default();
+    try!(json_to_message(json, message_builder.init_root()));
+
+    let words = serialize::write_message_to_words(&message_builder);
+    let message_reader = try!(serialize::read_message_from_words(&words, capnp::message::ReaderOptions::new()));
+
+    read_message_root(try!(message_reader.get_root()), registry)
+}
+
+/// Converts every binary message from `reader` into pretty-printed JSON.
+pub fn capnp_to_json<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> Result<(), Error> {
+    loop {
+        let at_eof = try!(reader.fill_buf()).is_empty();
+        if at_eof {
+            return Ok(());
+        }
+
+        let message_reader = try!(serialize::read_message(reader, capnp::message::ReaderOptions::new()));
+        let json = try!(message_to_json(try!(message_reader.get_root())));
+
+        try!(writeln!(writer, "{}", json.pretty()));
+    }
+}
+
+/// Converts every JSON message from `reader` into its binary form.
+pub fn json_to_capnp<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> Result<(), Error> {
+    while let Some(json) = try!(read_value(reader)) {
+        let mut message_builder = capnp::message::Builder::new_default();
+        try!(json_to_message(&json, message_builder.init_root()));
+
+        try!(serialize::write_message(writer, &message_builder));
+    }
+
+    Ok(())
+}
+
+/// Reads a single top-level JSON object, so a stream can contain several
+/// messages one after another.
+fn read_value<R: BufRead>(reader: &mut R) -> Result<Option<Json>, Error> {
+    let mut text = Vec::new();
+    let mut depth: usize = 0;
+    let (mut in_string, mut escaped) = (false, false);
+
+    for byte_result in reader.by_ref().bytes() {
+        let byte = try!(byte_result);
+
+        if text.is_empty() {
+            if (byte as char).is_whitespace() {
+                continue;
+            } else if byte != b'{' {
+                return Err(Error::new("Messages must be JSON objects"));
+            }
+        }
+
+        text.push(byte);
+
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+        } else {
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    if text.is_empty() {
+        return Ok(None);
+    } else if depth != 0 {
+        return Err(Error::new("Unexpected end of JSON"));
+    }
+
+    let text = try!(String::from_utf8(text).map_err(|_| Error::new("Invalid UTF-8 in JSON")));
+    Json::from_str(&text).map(Some).map_err(|_| Error::new("Invalid JSON"))
+}
+
+// capnp to JSON
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+fn variant(name: &str, value: Json) -> Json {
+    object(vec![(name, value)])
+}
+
+pub fn message_to_json(msg: message::Reader) -> Result<Json, Error> {
+    let message_type = match msg.get_type() {
+        Ok(MessageType::Start) => "start",
+        Ok(MessageType::Stop) => "stop",
+        Err(_) => return Err(Error::new("Invalid message type"))
+    };
+
+    let mut fields = vec![("type", Json::String(message_type.to_string()))];
+
+    match msg.get_body().which() {
+        Ok(message::body::None(())) => {},
+        Ok(message::body::Flame(flame_result)) => fields.push(("flame", try!(flame_to_json(try!(flame_result))))),
+        Err(_) => return Err(Error::new("Invalid message body"))
+    }
+
+    Ok(object(fields))
+}
+
+fn flame_to_json(flame: flame::Reader) -> Result<Json, Error> {
+    let mut fields = Vec::new();
+
+    if flame.has_transforms() {
+        let mut transforms = Array::new();
+        for transform_reader in try!(flame.get_transforms()).iter() {
+            transforms.push(try!(transform_to_json(transform_reader)));
+        }
+
+        fields.push(("transforms", Json::Array(transforms)));
+    }
+
+    if flame.has_final_transform() {
+        fields.push(("finalTransform", try!(transform_to_json(try!(flame.get_final_transform())))));
+    }
+
+    if flame.has_reset_transformation() {
+        fields.push(("resetTransformation", try!(affine_transformation_to_json(try!(flame.get_reset_transformation())))));
+    }
+
+    fields.push(("ttl", Json::I64(flame.get_ttl() as i64)));
+    fields.push(("fuse", Json::U64(flame.get_fuse() as u64)));
+    fields.push(("bound", Json::F64(flame.get_bound())));
+    fields.push(("seed", Json::U64(flame.get_seed())));
+
+    if flame.has_render_settings() {
+        fields.push(("renderSettings", try!(render_settings_to_json(try!(flame.get_render_settings())))));
+    }
+
+    Ok(object(fields))
+}
+
+fn transform_to_json(transform: transform::Reader) -> Result<Json, Error> {
+    let mut fields = Vec::new();
+
+    if transform.has_variations() {
+        let mut variations = Array::new();
+        for variation_reader in try!(transform.get_variations()).iter() {
+            variations.push(try!(variation_to_json(variation_reader)));
+        }
+
+        fields.push(("variations", Json::Array(variations)));
+    }
+
+    fields.push(("weight", Json::F64(transform.get_weight())));
+
+    if transform.has_xaos() {
+        let xaos = try!(transform.get_xaos());
+        fields.push(("xaos", Json::Array((0..xaos.len()).map(|i| Json::F64(xaos.get(i))).collect())));
+    }
+
+    if transform.has_pre() {
+        fields.push(("pre", try!(affine_transformation_to_json(try!(transform.get_pre())))));
+    }
+
+    if transform.has_post() {
+        fields.push(("post", try!(affine_transformation_to_json(try!(transform.get_post())))));
+    }
+
+    let coloring_method = match transform.get_coloring_method().which() {
+        Ok(transform::coloring_method::Noop(())) => Json::String("noop".to_string()),
+        Ok(transform::coloring_method::Distance(())) => Json::String("distance".to_string()),
+        Ok(transform::coloring_method::SingleColor(color)) => variant("singleColor", Json::F64(color)),
+        Err(_) => return Err(Error::new("Invalid coloring method"))
+    };
+    fields.push(("coloringMethod", coloring_method));
+    fields.push(("colorSpeed", Json::F64(transform.get_color_speed())));
+
+    Ok(object(fields))
+}
+
+fn variation_to_json(variation: variation::Reader) -> Result<Json, Error> {
+    let mut fields = vec![("name", Json::String(try!(variation.get_name()).to_string()))];
+
+    if variation.has_params() {
+        let params = try!(variation.get_params());
+        fields.push(("params", Json::Array((0..params.len()).map(|i| Json::F64(params.get(i))).collect())));
+    }
+
+    if variation.has_weight() {
+        fields.push(("weight", extent_to_json(try!(variation.get_weight()))));
+    }
+
+    if variation.has_named_params() {
+        let mut named_params = Object::new();
+        for param in try!(variation.get_named_params()).iter() {
+            named_params.insert(try!(param.get_name()).to_string(), Json::F64(param.get_value()));
+        }
+
+        fields.push(("namedParams", Json::Object(named_params)));
+    }
+
+    Ok(object(fields))
+}
+
+fn affine_transformation_to_json(at: affine_transformation::Reader) -> Result<Json, Error> {
+    let mut fields = Vec::new();
+
+    if at.has_translation() {
+        fields.push(("translation", extent_to_json(try!(at.get_translation()))));
+    }
+
+    if at.has_scale() {
+        fields.push(("scale", extent_to_json(try!(at.get_scale()))));
+    }
+
+    fields.push(("rotation", Json::F64(at.get_rotation())));
+
+    if at.has_matrix() {
+        let matrix = try!(at.get_matrix());
+        fields.push(("matrix", Json::Array((0..matrix.len()).map(|i| Json::F64(matrix.get(i))).collect())));
+    }
+
+    Ok(object(fields))
+}
+
+fn extent_to_json(extent: extent::Reader) -> Json {
+    object(vec![("x", Json::F64(extent.get_x())), ("y", Json::F64(extent.get_y()))])
+}
+
+fn render_settings_to_json(settings: render_settings::Reader) -> Result<Json, Error> {
+    let mut fields = vec![
+        ("width", Json::U64(settings.get_width() as u64)),
+        ("height", Json::U64(settings.get_height() as u64))
+    ];
+
+    if settings.has_camera() {
+        fields.push(("camera", try!(camera_to_json(try!(settings.get_camera())))));
+    }
+
+    let gamma = match settings.get_gamma().which() {
+        Ok(render_settings::gamma::Automatic(())) => Json::String("automatic".to_string()),
+        Ok(render_settings::gamma::Value(gamma)) => variant("value", Json::F64(gamma)),
+        Err(_) => return Err(Error::new("Invalid gamma"))
+    };
+    fields.push(("gamma", gamma));
+
+    let exposure = match settings.get_exposure().which() {
+        Ok(render_settings::exposure::Automatic(())) => Json::String("automatic".to_string()),
+        Ok(render_settings::exposure::Value(exposure)) => variant("value", Json::F64(exposure)),
+        Err(_) => return Err(Error::new("Invalid exposure"))
+    };
+    fields.push(("exposure", exposure));
+
+    if settings.has_background() {
+        fields.push(("background", color_to_json(try!(settings.get_background()))));
+    }
+
+    let palette = match settings.get_palette().which() {
+        Ok(render_settings::palette::None(())) => Json::String("none".to_string()),
+        Ok(render_settings::palette::File(path)) => variant("file", Json::String(try!(path).to_string())),
+        Ok(render_settings::palette::Stops(stops_result)) => {
+            let mut stops = Array::new();
+            for stop in try!(stops_result).iter() {
+                stops.push(try!(palette_stop_to_json(stop)));
+            }
+
+            variant("stops", Json::Array(stops))
+        },
+        Ok(render_settings::palette::Image(data)) => variant("image", Json::String(try!(data).to_base64(STANDARD))),
+        Err(_) => return Err(Error::new("Invalid palette"))
+    };
+    fields.push(("palette", palette));
+
+    if settings.has_density_estimation() {
+        fields.push(("densityEstimation", density_estimation_to_json(try!(settings.get_density_estimation()))));
+    }
+
+    Ok(object(fields))
+}
+
+fn density_estimation_to_json(estimation: density_estimation::Reader) -> Json {
+    object(vec![
+        ("maxRadius", Json::F64(estimation.get_max_radius())),
+        ("minRadius", Json::F64(estimation.get_min_radius())),
+        ("curve", Json::F64(estimation.get_curve()))
+    ])
+}
+
+fn camera_to_json(camera: camera::Reader) -> Result<Json, Error> {
+    let mut fields = Vec::new();
+
+    if camera.has_center() {
+        fields.push(("center", extent_to_json(try!(camera.get_center()))));
+    }
+
+    fields.push(("zoom", Json::F64(camera.get_zoom())));
+    fields.push(("rotation", Json::F64(camera.get_rotation())));
+
+    Ok(object(fields))
+}
+
+fn color_to_json(color: color::Reader) -> Json {
+    object(vec![
+        ("r", Json::F64(color.get_r())),
+        ("g", Json::F64(color.get_g())),
+        ("b", Json::F64(color.get_b())),
+        ("a", Json::F64(color.get_a()))
+    ])
+}
+
+fn palette_stop_to_json(stop: palette_stop::Reader) -> Result<Json, Error> {
+    let mut fields = vec![("position", Json::F64(stop.get_position()))];
+
+    if stop.has_color() {
+        fields.push(("color", color_to_json(try!(stop.get_color()))));
+    }
+
+    Ok(object(fields))
+}
+
+// JSON to capnp
+
+fn as_object(json: &Json) -> Result<&Object, Error> {
+    json.as_object().ok_or(Error::new("Expected a JSON object"))
+}
+
+fn as_array(json: &Json) -> Result<&Array, Error> {
+    json.as_array().ok_or(Error::new("Expected a JSON array"))
+}
+
+fn as_str(json: &Json) -> Result<&str, Error> {
+    json.as_string().ok_or(Error::new("Expected a JSON string"))
+}
+
+fn as_f64(json: &Json) -> Result<f64, Error> {
+    json.as_f64().ok_or(Error::new("Expected a JSON number"))
+}
+
+fn as_i64(json: &Json) -> Result<i64, Error> {
+    json.as_i64().ok_or(Error::new("Expected a JSON integer"))
+}
+
+fn as_u64(json: &Json) -> Result<u64, Error> {
+    json.as_u64().ok_or(Error::new("Expected a non-negative JSON integer"))
+}
+
+fn as_i32(json: &Json) -> Result<i32, Error> {
+    let value = try!(as_i64(json));
+
+    if value < (i32::MIN as i64) || value > (i32::MAX as i64) {
+        return Err(Error::new("Expected a JSON integer that fits in 32 bits"));
+    }
+
+    Ok(value as i32)
+}
+
+fn as_u32(json: &Json) -> Result<u32, Error> {
+    let value = try!(as_u64(json));
+
+    if value > (u32::MAX as u64) {
+        return Err(Error::new("Expected a non-negative JSON integer that fits in 32 bits"));
+    }
+
+    Ok(value as u32)
+}
+
+/// Splits a union into the name of its variant and the variant's value, if
+/// it has one.
+fn as_variant(json: &Json) -> Result<(&str, Option<&Json>), Error> {
+    match *json {
+        Json::String(ref name) => Ok((name, None)),
+        Json::Object(ref fields) if fields.len() == 1 => {
+            let (name, value) = fields.iter().next().unwrap();
+            Ok((name, Some(value)))
+        },
+        _ => Err(Error::new("Expected a union variant in JSON"))
+    }
+}
+
+/// Takes the fields of an object, rejecting the ones that aren't `known` so
+/// typos don't silently fall back to defaults.
+fn as_fields<'a>(json: &'a Json, known: &[&str]) -> Result<&'a Object, Error> {
+    let fields = try!(as_object(json));
+
+    for key in fields.keys() {
+        if !known.contains(&&key[..]) {
+            return Err(Error::from(format!("Unknown field \"{}\" in JSON", key)));
+        }
+    }
+
+    Ok(fields)
+}
+
+fn variant_value(value: Option<&Json>) -> Result<&Json, Error> {
+    value.ok_or(Error::new("Union variant is missing its value in JSON"))
+}
+
+fn json_to_f64_list(json: &Json) -> Result<Vec<f64>, Error> {
+    let mut values = Vec::new();
+    for value in try!(as_array(json)).iter() {
+        values.push(try!(as_f64(value)));
+    }
+
+    Ok(values)
+}
+
+pub fn json_to_message(json: &Json, mut msg: message::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["type", "flame"]));
+
+    match fields.get("type").and_then(|message_type| message_type.as_string()) {
+        Some("start") => msg.set_type(MessageType::Start),
+        Some("stop") => msg.set_type(MessageType::Stop),
+        _ => return Err(Error::new("Invalid message type"))
+    }
+
+    match fields.get("flame") {
+        Some(flame) => json_to_flame(flame, msg.get_body().init_flame()),
+        None => {
+            msg.get_body().set_none(());
+            Ok(())
+        }
+    }
+}
+
+fn json_to_flame(json: &Json, mut flame: flame::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["transforms", "finalTransform", "resetTransformation", "ttl", "fuse", "bound", "seed", "renderSettings"]));
+
+    if let Some(transforms) = fields.get("transforms") {
+        let transforms = try!(as_array(transforms));
+        let mut transform_list = flame.borrow().init_transforms(transforms.len() as u32);
+
+        for (i, transform) in transforms.iter().enumerate() {
+            try!(json_to_transform(transform, transform_list.borrow().get(i as u32)));
+        }
+    }
+
+    if let Some(transform) = fields.get("finalTransform") {
+        try!(json_to_transform(transform, flame.borrow().init_final_transform()));
+    }
+
+    if let Some(at) = fields.get("resetTransformation") {
+        try!(json_to_affine_transformation(at, flame.borrow().init_reset_transformation()));
+    }
+
+    if let Some(ttl) = fields.get("ttl") {
+        flame.set_ttl(try!(as_i32(ttl)));
+    }
+
+    if let Some(fuse) = fields.get("fuse") {
+        flame.set_fuse(try!(as_u32(fuse)));
+    }
+
+    if let Some(bound) = fields.get("bound") {
+        flame.set_bound(try!(as_f64(bound)));
+    }
+
+    if let Some(seed) = fields.get("seed") {
+        flame.set_seed(try!(as_u64(seed)));
+    }
+
+    if let Some(settings) = fields.get("renderSettings") {
+        try!(json_to_render_settings(settings, flame.init_render_settings()));
+    }
+
+    Ok(())
+}
+
+fn json_to_transform(json: &Json, mut transform: transform::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["variations", "weight", "pre", "post", "coloringMethod", "colorSpeed", "xaos"]));
+
+    if let Some(weight) = fields.get("weight") {
+        transform.set_weight(try!(as_f64(weight)));
+    }
+
+    if let Some(xaos) = fields.get("xaos") {
+        let xaos = try!(json_to_f64_list(xaos));
+        let mut row = transform.borrow().init_xaos(xaos.len() as u32);
+
+        for (i, value) in xaos.iter().enumerate() {
+            row.set(i as u32, *value);
+        }
+    }
+
+    if let Some(at) = fields.get("pre") {
+        try!(json_to_affine_transformation(at, transform.borrow().init_pre()));
+    }
+
+    if let Some(at) = fields.get("post") {
+        try!(json_to_affine_transformation(at, transform.borrow().init_post()));
+    }
+
+    if let Some(speed) = fields.get("colorSpeed") {
+        transform.set_color_speed(try!(as_f64(speed)));
+    }
+
+    if let Some(coloring_method) = fields.get("coloringMethod") {
+        match try!(as_variant(coloring_method)) {
+            ("noop", _) => transform.borrow().get_coloring_method().set_noop(()),
+            ("distance", _) => transform.borrow().get_coloring_method().set_distance(()),
+            ("singleColor", value) => {
+                let color = try!(as_f64(try!(variant_value(value))));
+                transform.borrow().get_coloring_method().set_single_color(color);
+            },
+            _ => return Err(Error::new("Invalid coloring method"))
+        }
+    }
+
+    if let Some(variations) = fields.get("variations") {
+        let variations = try!(as_array(variations));
+        let mut variation_list = transform.init_variations(variations.len() as u32);
+
+        for (i, variation) in variations.iter().enumerate() {
+            try!(json_to_variation(variation, variation_list.borrow().get(i as u32)));
+        }
+    }
+
+    Ok(())
+}
+
+fn json_to_variation(json: &Json, mut variation: variation::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["name", "params", "weight", "namedParams"]));
+
+    match fields.get("name") {
+        Some(name) => variation.set_name(try!(as_str(name))),
+        None => return Err(Error::new("Variation is missing its name"))
+    }
+
+    if let Some(params) = fields.get("params") {
+        let params = try!(json_to_f64_list(params));
+        let mut param_list = variation.borrow().init_params(params.len() as u32);
+
+        for (i, param) in params.iter().enumerate() {
+            param_list.set(i as u32, *param);
+        }
+    }
+
+    if let Some(weight) = fields.get("weight") {
+        try!(json_to_extent(weight, variation.borrow().init_weight()));
+    }
+
+    if let Some(named_params) = fields.get("namedParams") {
+        let named_params = try!(as_object(named_params));
+        let mut param_list = variation.init_named_params(named_params.len() as u32);
+
+        for (i, (name, value)) in named_params.iter().enumerate() {
+            let mut param = param_list.borrow().get(i as u32);
+            param.set_name(name);
+            param.set_value(try!(as_f64(value)));
+        }
+    }
+
+    Ok(())
+}
+
+fn json_to_affine_transformation(json: &Json, mut at: affine_transformation::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["translation", "scale", "rotation", "matrix"]));
+
+    if let Some(translation) = fields.get("translation") {
+        try!(json_to_extent(translation, at.borrow().init_translation()));
+    }
+
+    if let Some(scale) = fields.get("scale") {
+        try!(json_to_extent(scale, at.borrow().init_scale()));
+    }
+
+    if let Some(rotation) = fields.get("rotation") {
+        at.set_rotation(try!(as_f64(rotation)));
+    }
+
+    if let Some(matrix) = fields.get("matrix") {
+        let matrix = try!(json_to_f64_list(matrix));
+        let mut coefficients = at.init_matrix(matrix.len() as u32);
+
+        for (i, coefficient) in matrix.iter().enumerate() {
+            coefficients.set(i as u32, *coefficient);
+        }
+    }
+
+    Ok(())
+}
+
+fn json_to_extent(json: &Json, mut extent: extent::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["x", "y"]));
+
+    match (fields.get("x"), fields.get("y")) {
+        (Some(x), Some(y)) => {
+            extent.set_x(try!(as_f64(x)));
+            extent.set_y(try!(as_f64(y)));
+            Ok(())
+        },
+        _ => Err(Error::new("Extents need both x and y"))
+    }
+}
+
+fn json_to_render_settings(json: &Json, mut settings: render_settings::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["width", "height", "camera", "gamma", "exposure", "background", "palette", "densityEstimation"]));
+
+    if let Some(width) = fields.get("width") {
+        settings.set_width(try!(as_u32(width)));
+    }
+
+    if let Some(height) = fields.get("height") {
+        settings.set_height(try!(as_u32(height)));
+    }
+
+    if let Some(camera) = fields.get("camera") {
+        try!(json_to_camera(camera, settings.borrow().init_camera()));
+    }
+
+    if let Some(gamma) = fields.get("gamma") {
+        match try!(as_variant(gamma)) {
+            ("automatic", _) => settings.borrow().get_gamma().set_automatic(()),
+            ("value", value) => {
+                let gamma = try!(as_f64(try!(variant_value(value))));
+                settings.borrow().get_gamma().set_value(gamma);
+            },
+            _ => return Err(Error::new("Invalid gamma"))
+        }
+    }
+
+    if let Some(exposure) = fields.get("exposure") {
+        match try!(as_variant(exposure)) {
+            ("automatic", _) => settings.borrow().get_exposure().set_automatic(()),
+            ("value", value) => {
+                let exposure = try!(as_f64(try!(variant_value(value))));
+                settings.borrow().get_exposure().set_value(exposure);
+            },
+            _ => return Err(Error::new("Invalid exposure"))
+        }
+    }
+
+    if let Some(background) = fields.get("background") {
+        try!(json_to_color(background, settings.borrow().init_background()));
+    }
+
+    if let Some(estimation) = fields.get("densityEstimation") {
+        try!(json_to_density_estimation(estimation, settings.borrow().init_density_estimation()));
+    }
+
+    if let Some(palette) = fields.get("palette") {
+        match try!(as_variant(palette)) {
+            ("none", _) => settings.get_palette().set_none(()),
+            ("file", value) => settings.get_palette().set_file(try!(as_str(try!(variant_value(value))))),
+            ("stops", value) => {
+                let stops = try!(as_array(try!(variant_value(value))));
+                let mut stop_list = settings.get_palette().init_stops(stops.len() as u32);
+
+                for (i, stop) in stops.iter().enumerate() {
+                    try!(json_to_palette_stop(stop, stop_list.borrow().get(i as u32)));
+                }
+            },
+            ("image", value) => {
+                let encoded = try!(as_str(try!(variant_value(value))));
+                let data = try!(encoded.from_base64().map_err(|_| Error::new("Invalid base64 in palette image")));
+                settings.get_palette().set_image(&data);
+            },
+            _ => return Err(Error::new("Invalid palette"))
+        }
+    }
+
+    Ok(())
+}
+
+fn json_to_camera(json: &Json, mut camera: camera::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["center", "zoom", "rotation"]));
+
+    if let Some(center) = fields.get("center") {
+        try!(json_to_extent(center, camera.borrow().init_center()));
+    }
+
+    if let Some(zoom) = fields.get("zoom") {
+        camera.set_zoom(try!(as_f64(zoom)));
+    }
+
+    if let Some(rotation) = fields.get("rotation") {
+        camera.set_rotation(try!(as_f64(rotation)));
+    }
+
+    Ok(())
+}
+
+fn json_to_density_estimation(json: &Json, mut estimation: density_estimation::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["maxRadius", "minRadius", "curve"]));
+
+    if let Some(max_radius) = fields.get("maxRadius") {
+        estimation.set_max_radius(try!(as_f64(max_radius)));
+    }
+
+    if let Some(min_radius) = fields.get("minRadius") {
+        estimation.set_min_radius(try!(as_f64(min_radius)));
+    }
+
+    if let Some(curve) = fields.get("curve") {
+        estimation.set_curve(try!(as_f64(curve)));
+    }
+
+    Ok(())
+}
+
+fn json_to_color(json: &Json, mut color: color::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["r", "g", "b", "a"]));
+
+    if let Some(r) = fields.get("r") {
+        color.set_r(try!(as_f64(r)));
+    }
+
+    if let Some(g) = fields.get("g") {
+        color.set_g(try!(as_f64(g)));
+    }
+
+    if let Some(b) = fields.get("b") {
+        color.set_b(try!(as_f64(b)));
+    }
+
+    if let Some(a) = fields.get("a") {
+        color.set_a(try!(as_f64(a)));
+    }
+
+    Ok(())
+}
+
+fn json_to_palette_stop(json: &Json, mut stop: palette_stop::Builder) -> Result<(), Error> {
+    let fields = try!(as_fields(json, &["position", "color"]));
+
+    if let Some(position) = fields.get("position") {
+        stop.set_position(try!(as_f64(position)));
+    }
+
+    if let Some(color) = fields.get("color") {
+        try!(json_to_color(color, stop.init_color()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_message, json_to_capnp, capnp_to_json};
+
+    const FLAMES: &'static str = r#"
+        {
+            "type": "start",
+            "flame": {
+                "transforms": [
+                    {
+                        "variations": [
+                            {"name": "Julian", "namedParams": {"power": 3, "distance": 1}, "weight": {"x": 0.7, "y": 0.6}},
+                            {"name": "DeJong", "params": [1.66, -0.68, 1.47, 1.79]}
+                        ],
+                        "weight": 2,
+                        "xaos": [0, 2],
+                        "pre": {"matrix": [0.5, -0.25, 0.1, 0.25, 0.5, -0.2]},
+                        "post": {"translation": {"x": 0.1, "y": -0.1}, "scale": {"x": 2, "y": 1}, "rotation": 0.5},
+                        "coloringMethod": {"singleColor": 0.2},
+                        "colorSpeed": 0.3
+                    },
+                    {"variations": [{"name": "Linear"}], "coloringMethod": "distance"}
+                ],
+                "finalTransform": {"variations": [{"name": "Spherical"}], "coloringMethod": "noop"},
+                "resetTransformation": {"scale": {"x": 2, "y": 2}},
+                "ttl": 150,
+                "fuse": 10,
+                "bound": 1e6,
+                "seed": 42,
+                "renderSettings": {
+                    "width": 640,
+                    "height": 480,
+                    "camera": {"center": {"x": 0.5, "y": -0.5}, "zoom": 1.5, "rotation": 0.1},
+                    "gamma": {"value": 2.2},
+                    "exposure": "automatic",
+                    "background": {"r": 0.1, "g": 0.2, "b": 0.3},
+                    "palette": {"stops": [{"position": 0, "color": {"r": 1}}, {"position": 1, "color": {"b": 1, "a": 0.5}}]},
+                    "densityEstimation": {"maxRadius": 5, "minRadius": 1, "curve": 0.5}
+                }
+            }
+        }
+        {"type": "start", "flame": {"transforms": [{}], "ttl": 30, "renderSettings": {"palette": {"image": "AAEC"}}}}
+        {"type": "stop"}
+    "#;
+
+    fn to_capnp(json: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        json_to_capnp(&mut &json[..], &mut output).unwrap();
+
+        output
+    }
+
+    fn to_json(capnp: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        capnp_to_json(&mut &capnp[..], &mut output).unwrap();
+
+        output
+    }
+
+    #[test]
+    fn json_capnp_json_gives_identical_bytes() {
+        let capnp = to_capnp(FLAMES.as_bytes());
+        let json = to_json(&capnp);
+
+        assert!(capnp == to_capnp(&json));
+        assert!(json == to_json(&to_capnp(&json)), "{}", String::from_utf8_lossy(&json));
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        let documents = [
+            "[]",
+            "{",
+            r#"{"type": "start""#,
+            r#"{"type": "go"}"#,
+            r#"{"type": "start", "flame": []}"#,
+            r#"{"type": "start", "flame": {"ttl": 4294967296}}"#,
+            r#"{"type": "start", "flame": {"ttl": 1.5}}"#,
+            r#"{"type": "start", "flame": {"fuse": -1}}"#,
+            r#"{"type": "start", "flame": {"fuse ": 10}}"#,
+            r#"{"type": "start", "flame": {"seed": "42"}}"#,
+            r#"{"type": "start", "flame": {"transforms": [{"colourSpeed": 0.5}]}}"#,
+            r#"{"type": "start", "flame": {"transforms": [{"xaos": [1, "a"]}]}}"#,
+            r#"{"type": "start", "flame": {"transforms": [{"coloringMethod": "rainbow"}]}}"#,
+            r#"{"type": "start", "flame": {"transforms": [{"coloringMethod": {"singleColor": 0.5, "distance": null}}]}}"#,
+            r#"{"type": "start", "flame": {"transforms": [{"variations": [{"params": [1]}]}]}}"#,
+            r#"{"type": "start", "flame": {"transforms": [{"pre": {"translation": {"x": 1}}}]}}"#,
+            r#"{"type": "start", "flame": {"renderSettings": {"width": 5000000000}}}"#,
+            r#"{"type": "start", "flame": {"renderSettings": {"gamma": {"value": "high"}}}}"#,
+            r#"{"type": "start", "flame": {"renderSettings": {"palette": {"image": "not base64!"}}}}"#,
+            r#"{"type": "start", "flame": {"renderSettings": {"camera": {"zoom": 1, "pan": 0}}}}"#
+        ];
+
+        for document in documents.iter() {
+            assert!(json_to_capnp(&mut document.as_bytes(), &mut Vec::new()).is_err(), "{}", document);
+        }
+    }
+
+    #[test]
+    fn flames_need_a_ttl_of_at_least_2() {
+        let transforms = r#""transforms": [{"variations": [{"name": "Linear"}]}]"#;
+
+        for ttl in &["", r#", "ttl": 0"#, r#", "ttl": 1"#, r#", "ttl": -5"#] {
+            let json = format!(r#"{{"type": "start", "flame": {{{}{}}}}}"#, transforms, ttl);
+            assert!(read_message(&mut json.as_bytes()).is_err(), "{}", json);
+        }
+
+        let json = format!(r#"{{"type": "start", "flame": {{{}, "ttl": 2}}}}"#, transforms);
+        assert!(read_message(&mut json.as_bytes()).unwrap().is_some());
+    }
+}
@@ -1,27 +1,65 @@
 mod error;
+mod writer;
+pub mod json;
 pub use self::error::Error;
+pub use self::writer::{write_message, write_flame};
+pub use self::json::read_message as read_json_message;
+pub use self::json::read_message_with_registry as read_json_message_with_registry;
 
 use std::io::Read;
-use types::{Message, Variation, Number};
+use types::{Message, Variation, Number, Point};
 use types::system::*;
 use types::transform::*;
 use types::affine_transformation::*;
 use types::coloring_method;
+use types::render_settings::{RenderSettings, Camera, DensityEstimation, PaletteSource, PaletteStop};
 use variations;
+use variations::VariationRegistry;
 
-use chaoskit_capnp::{message, MessageType, flame, transform, variation, affine_transformation};
+use chaoskit_capnp::{message, MessageType, flame, transform, variation, affine_transformation, render_settings, camera, density_estimation};
 use capnp;
 use capnp::serialize;
 
+/// How messages are encoded on the wire.
+#[derive(Copy, Clone, Debug)]
+pub enum Format {
+    Capnp,
+    Json
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Format, &'static str> {
+        match format {
+            "capnp" => Ok(Format::Capnp),
+            "json" => Ok(Format::Json),
+            _ => Err("Format must be either capnp or json")
+        }
+    }
+}
+
+/// Reads a message, creating its variations with the global registry.
 pub fn read_message<R: Read>(reader: &mut R) -> Result<Message, Error> {
     let message_reader = try!(serialize::read_message(reader, capnp::message::ReaderOptions::new()));
     let msg: message::Reader = try!(message_reader.get_root());
 
+    // Only locked once the message is in, so registering doesn't wait on clients
+    let registry = variations::global_registry().read().unwrap();
+    read_message_root(msg, &registry)
+}
+
+pub fn read_message_with_registry<R: Read>(reader: &mut R, registry: &VariationRegistry) -> Result<Message, Error> {
+    let message_reader = try!(serialize::read_message(reader, capnp::message::ReaderOptions::new()));
+    let msg: message::Reader = try!(message_reader.get_root());
+
+    read_message_root(msg, registry)
+}
+
+fn read_message_root(msg: message::Reader, registry: &VariationRegistry) -> Result<Message, Error> {
     match msg.get_type() {
         Ok(MessageType::Start) => match msg.get_body().which() {
             Ok(message::body::Flame(flame_result)) => {
                 let flame_reader = try!(flame_result);
-                let flame = try!(read_flame(flame_reader));
+                let flame = try!(read_flame(flame_reader, registry));
                 Ok(Message::Start(flame))
             }
             _ => Err(Error::new("Invalid message body"))
@@ -31,19 +69,24 @@ pub fn read_message<R: Read>(reader: &mut R) -> Result<Message, Error> {
     }
 }
 
-fn read_flame(flame: flame::Reader) -> Result<System, Error> {
+fn read_flame(flame: flame::Reader, registry: &VariationRegistry) -> Result<System, Error> {
     let mut builder = SystemBuilder::new();
 
-    for transform_reader in try!(flame.get_transforms()).iter() {
+    for (i, transform_reader) in try!(flame.get_transforms()).iter().enumerate() {
         let weight = transform_reader.get_weight();
-        let transform = try!(read_transform(transform_reader));
+        let transform = try!(read_transform(transform_reader, registry));
 
         builder = builder.add_weighted_transform(transform, weight);
+
+        if transform_reader.has_xaos() {
+            let xaos = try!(transform_reader.get_xaos());
+            builder = builder.xaos(i, (0..xaos.len()).map(|j| xaos.get(j) as Number).collect());
+        }
     }
 
     if flame.has_final_transform() {
         let final_transform = try!(flame.get_final_transform());
-        let transform = try!(read_transform(final_transform));
+        let transform = try!(read_transform(final_transform, registry));
 
         builder = builder.final_transform(transform);
     }
@@ -55,16 +98,26 @@ fn read_flame(flame: flame::Reader) -> Result<System, Error> {
         builder = builder.reset_transformation(af);
     }
 
-    let ttl = flame.get_ttl();
+    if flame.has_render_settings() {
+        let settings_reader = try!(flame.get_render_settings());
+        let settings = try!(read_render_settings(settings_reader));
+
+        builder = builder.render_settings(settings);
+    }
 
-    Ok(builder.ttl(ttl).finalize())
+    let seed = flame.get_seed();
+    if seed != 0 {
+        builder = builder.seed(seed);
+    }
+
+    Ok(try!(builder.ttl(flame.get_ttl()).fuse(flame.get_fuse()).bound(flame.get_bound() as Number).finalize()))
 }
 
-fn read_transform(transform: transform::Reader) -> Result<Transform, Error> {
+fn read_transform(transform: transform::Reader, registry: &VariationRegistry) -> Result<Transform, Error> {
     let mut builder = TransformBuilder::new();
 
     for variation_reader in try!(transform.get_variations()).iter() {
-        let variation: Box<Variation> = try!(read_variation(variation_reader));
+        let variation: Box<Variation> = try!(read_variation(variation_reader, registry));
 
         if variation_reader.has_weight() {
             let weight = try!(variation_reader.get_weight());
@@ -90,7 +143,14 @@ fn read_transform(transform: transform::Reader) -> Result<Transform, Error> {
             builder = builder.coloring_method(Box::new(coloring_method::Distance));
         },
         Ok(transform::coloring_method::SingleColor(color)) => {
-            builder = builder.coloring_method(Box::new(coloring_method::SingleColor::new(color as Number)));
+            let speed = transform.get_color_speed() as Number;
+
+            // Anything else leaves particle colors outside the palette
+            if !(color >= 0.0 && color <= 1.0 && speed >= 0.0 && speed <= 1.0) {
+                return Err(Error::new("Single color and color speed must be between 0 and 1"));
+            }
+
+            builder = builder.coloring_method(Box::new(coloring_method::SingleColor::with_speed(color as Number, speed)));
         },
         Err(capnp::NotInSchema(_)) => {
             return Err(Error::new("Invalid coloring method"));
@@ -100,7 +160,7 @@ fn read_transform(transform: transform::Reader) -> Result<Transform, Error> {
     Ok(builder.finalize())
 }
 
-fn read_variation(variation: variation::Reader) -> Result<Box<Variation>, Error> {
+fn read_variation(variation: variation::Reader, registry: &VariationRegistry) -> Result<Box<Variation>, Error> {
     let name = try!(variation.get_name());
     let params = try!(variation.get_params());
 
@@ -110,10 +170,30 @@ fn read_variation(variation: variation::Reader) -> Result<Box<Variation>, Error>
         params_vec.push(params.get(i) as Number);
     }
 
-    variations::make_variation(name, &params_vec).map_err(|e| Error::from(e))
+    let mut named_params = Vec::new();
+    if variation.has_named_params() {
+        for param in try!(variation.get_named_params()).iter() {
+            named_params.push((try!(param.get_name()), param.get_value() as Number));
+        }
+    }
+
+    registry.make(name, &params_vec, &named_params).map_err(|e| Error::from(e))
 }
 
 fn read_affine_transformation(at: affine_transformation::Reader) -> Result<AffineTransformation, Error> {
+    if at.has_matrix() {
+        let matrix = try!(at.get_matrix());
+
+        if matrix.len() != 6 {
+            return Err(Error::new("Affine transformation matrix must have 6 coefficients"));
+        }
+
+        return Ok(AffineTransformation::from_coefficients(
+            matrix.get(0) as Number, matrix.get(1) as Number, matrix.get(2) as Number,
+            matrix.get(3) as Number, matrix.get(4) as Number, matrix.get(5) as Number
+        ));
+    }
+
     let translation = try!(at.get_translation());
     let scale = try!(at.get_scale());
 
@@ -123,3 +203,76 @@ fn read_affine_transformation(at: affine_transformation::Reader) -> Result<Affin
         .scale(scale.get_x() as Number, scale.get_y() as Number)
         .finalize())
 }
+
+fn read_render_settings(settings: render_settings::Reader) -> Result<RenderSettings, Error> {
+    let mut render_settings = RenderSettings::new();
+
+    render_settings.width = settings.get_width() as usize;
+    render_settings.height = settings.get_height() as usize;
+
+    if settings.has_camera() {
+        render_settings.camera = try!(read_camera(try!(settings.get_camera())));
+    }
+
+    render_settings.gamma = match settings.get_gamma().which() {
+        Ok(render_settings::gamma::Automatic(())) => None,
+        Ok(render_settings::gamma::Value(gamma)) => Some(gamma as Number),
+        Err(capnp::NotInSchema(_)) => return Err(Error::new("Invalid gamma"))
+    };
+
+    render_settings.exposure = match settings.get_exposure().which() {
+        Ok(render_settings::exposure::Automatic(())) => None,
+        Ok(render_settings::exposure::Value(exposure)) => Some(exposure as Number),
+        Err(capnp::NotInSchema(_)) => return Err(Error::new("Invalid exposure"))
+    };
+
+    if settings.has_background() {
+        let background = try!(settings.get_background());
+        render_settings.background = (background.get_r() as Number, background.get_g() as Number, background.get_b() as Number);
+    }
+
+    render_settings.palette = match settings.get_palette().which() {
+        Ok(render_settings::palette::None(())) => None,
+        Ok(render_settings::palette::File(path)) => Some(PaletteSource::File(try!(path).to_string())),
+        Ok(render_settings::palette::Stops(stops_result)) => {
+            let mut stops = Vec::new();
+
+            for stop_reader in try!(stops_result).iter() {
+                let color = try!(stop_reader.get_color());
+
+                stops.push(PaletteStop {
+                    position: stop_reader.get_position() as Number,
+                    color: (color.get_r() as Number, color.get_g() as Number, color.get_b() as Number, color.get_a() as Number)
+                });
+            }
+
+            Some(PaletteSource::Stops(stops))
+        },
+        Ok(render_settings::palette::Image(data)) => Some(PaletteSource::Image(try!(data).to_vec())),
+        Err(capnp::NotInSchema(_)) => return Err(Error::new("Invalid palette"))
+    };
+
+    if settings.has_density_estimation() {
+        render_settings.density_estimation = Some(read_density_estimation(try!(settings.get_density_estimation())));
+    }
+
+    Ok(render_settings)
+}
+
+fn read_density_estimation(estimation: density_estimation::Reader) -> DensityEstimation {
+    DensityEstimation {
+        max_radius: estimation.get_max_radius() as Number,
+        min_radius: estimation.get_min_radius() as Number,
+        curve: estimation.get_curve() as Number
+    }
+}
+
+fn read_camera(camera: camera::Reader) -> Result<Camera, Error> {
+    let center = try!(camera.get_center());
+
+    Ok(Camera {
+        center: Point::from_xy(center.get_x() as Number, center.get_y() as Number),
+        zoom: camera.get_zoom() as Number,
+        rotation: camera.get_rotation() as Number
+    })
+}
@@ -0,0 +1,256 @@
+use std::io::Write;
+use types::{Message, System, Transform, AffineTransformation};
+use types::render_settings::{RenderSettings, PaletteSource};
+use interop::Error;
+
+use chaoskit_capnp::{message, MessageType, flame, transform, affine_transformation, render_settings};
+use capnp;
+use capnp::serialize;
+
+pub fn write_message<W: Write>(writer: &mut W, msg: &Message) -> Result<(), Error> {
+    let mut message_builder = capnp::message::Builder::new_default();
+
+    {
+        let mut root = message_builder.init_root::<message::Builder>();
+
+        match *msg {
+            Message::Start(ref system) => {
+                root.set_type(MessageType::Start);
+                try!(write_flame(system, root.get_body().init_flame()));
+            },
+            Message::Stop => {
+                root.set_type(MessageType::Stop);
+                root.get_body().set_none(());
+            }
+        }
+    }
+
+    try!(serialize::write_message(writer, &message_builder));
+    Ok(())
+}
+
+pub fn write_flame(system: &System, mut flame: flame::Builder) -> Result<(), Error> {
+    let transforms = system.transforms();
+
+    {
+        let mut transform_list = flame.borrow().init_transforms(transforms.len() as u32);
+
+        let xaos = system.xaos();
+
+        for (i, &(transform, weight)) in transforms.iter().enumerate() {
+            let mut transform_builder = transform_list.borrow().get(i as u32);
+            transform_builder.set_weight(weight);
+
+            if !xaos[i].is_empty() {
+                let mut row = transform_builder.borrow().init_xaos(xaos[i].len() as u32);
+
+                for (j, value) in xaos[i].iter().enumerate() {
+                    row.set(j as u32, *value);
+                }
+            }
+
+            try!(write_transform(transform, transform_builder));
+        }
+    }
+
+    try!(write_transform(&system.final_transform, flame.borrow().init_final_transform()));
+    write_affine_transformation(&system.reset_transformation, flame.borrow().init_reset_transformation());
+
+    flame.set_ttl(system.ttl);
+    flame.set_fuse(system.fuse);
+    flame.set_bound(system.bound);
+    flame.set_seed(system.seed.unwrap_or(0));
+
+    write_render_settings(&system.render_settings, flame.init_render_settings());
+
+    Ok(())
+}
+
+fn write_transform(transform: &Transform, mut builder: transform::Builder) -> Result<(), Error> {
+    write_affine_transformation(transform.pre(), builder.borrow().init_pre());
+    write_affine_transformation(transform.post(), builder.borrow().init_post());
+
+    let coloring_method = transform.coloring_method();
+    let coloring_params = coloring_method.params();
+    match coloring_method.name() {
+        "noop" => builder.borrow().get_coloring_method().set_noop(()),
+        "distance" => builder.borrow().get_coloring_method().set_distance(()),
+        "singleColor" if coloring_params.len() == 2 => {
+            builder.borrow().get_coloring_method().set_single_color(coloring_params[0]);
+            builder.set_color_speed(coloring_params[1]);
+        },
+        _ => return Err(Error::new("Unknown coloring method"))
+    }
+
+    let variations = transform.variations();
+    let mut variation_list = builder.init_variations(variations.len() as u32);
+
+    for (i, &(ref variation, weight_x, weight_y)) in variations.iter().enumerate() {
+        let mut variation_builder = variation_list.borrow().get(i as u32);
+        variation_builder.set_name(variation.name());
+
+        {
+            let params = variation.params();
+            let mut param_list = variation_builder.borrow().init_params(params.len() as u32);
+
+            for (j, param) in params.iter().enumerate() {
+                param_list.set(j as u32, *param);
+            }
+        }
+
+        let mut weight = variation_builder.init_weight();
+        weight.set_x(weight_x);
+        weight.set_y(weight_y);
+    }
+
+    Ok(())
+}
+
+fn write_affine_transformation(transformation: &AffineTransformation, builder: affine_transformation::Builder) {
+    let coefficients = transformation.coefficients();
+    let mut matrix = builder.init_matrix(coefficients.len() as u32);
+
+    for (i, coefficient) in coefficients.iter().enumerate() {
+        matrix.set(i as u32, *coefficient);
+    }
+}
+
+fn write_render_settings(settings: &RenderSettings, mut builder: render_settings::Builder) {
+    builder.set_width(settings.width as u32);
+    builder.set_height(settings.height as u32);
+
+    {
+        let mut camera = builder.borrow().init_camera();
+        camera.set_zoom(settings.camera.zoom);
+        camera.set_rotation(settings.camera.rotation);
+
+        let mut center = camera.init_center();
+        center.set_x(settings.camera.center.x);
+        center.set_y(settings.camera.center.y);
+    }
+
+    match settings.gamma {
+        Some(gamma) => builder.borrow().get_gamma().set_value(gamma),
+        None => builder.borrow().get_gamma().set_automatic(())
+    }
+
+    match settings.exposure {
+        Some(exposure) => builder.borrow().get_exposure().set_value(exposure),
+        None => builder.borrow().get_exposure().set_automatic(())
+    }
+
+    {
+        let (r, g, b) = settings.background;
+        let mut background = builder.borrow().init_background();
+        background.set_r(r);
+        background.set_g(g);
+        background.set_b(b);
+    }
+
+    if let Some(estimation) = settings.density_estimation {
+        let mut estimation_builder = builder.borrow().init_density_estimation();
+        estimation_builder.set_max_radius(estimation.max_radius);
+        estimation_builder.set_min_radius(estimation.min_radius);
+        estimation_builder.set_curve(estimation.curve);
+    }
+
+    match settings.palette {
+        None => builder.get_palette().set_none(()),
+        Some(PaletteSource::File(ref path)) => builder.get_palette().set_file(path),
+        Some(PaletteSource::Image(ref data)) => builder.get_palette().set_image(data),
+        Some(PaletteSource::Stops(ref stops)) => {
+            let mut stop_list = builder.get_palette().init_stops(stops.len() as u32);
+
+            for (i, stop) in stops.iter().enumerate() {
+                let mut stop_builder = stop_list.borrow().get(i as u32);
+                stop_builder.set_position(stop.position);
+
+                let (r, g, b, a) = stop.color;
+                let mut color = stop_builder.init_color();
+                color.set_r(r);
+                color.set_g(g);
+                color.set_b(b);
+                color.set_a(a);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_message;
+    use interop::read_message;
+    use types::{Message, System, AffineTransformation};
+    use types::system::SystemBuilder;
+    use types::transform::TransformBuilder;
+    use types::coloring_method::{Distance, SingleColor};
+    use types::render_settings::{RenderSettings, DensityEstimation, PaletteSource, PaletteStop};
+    use variations;
+
+    fn system() -> System {
+        let julian = variations::make_variation_with_named_params("Julian", &[], &[("power", 3.0), ("distance", 1.0)]).unwrap();
+        let first = TransformBuilder::new()
+            .pre(AffineTransformation::from_coefficients(0.5, -0.25, 0.1, 0.25, 0.5, -0.2))
+            .post(AffineTransformation::from_coefficients(1.0, 0.1, 0.0, -0.1, 1.0, 0.3))
+            .add_boxed_2d_weighted_variation(julian, 0.7, 0.6)
+            .coloring_method(Box::new(SingleColor::with_speed(0.2, 0.3)))
+            .finalize();
+        let second = TransformBuilder::new()
+            .add_boxed_variation(variations::make_variation("DeJong", &[1.66, -0.68, 1.47, 1.79]).unwrap())
+            .coloring_method(Box::new(Distance))
+            .finalize();
+        let final_transform = TransformBuilder::new()
+            .add_boxed_variation(variations::make_variation("Spherical", &[]).unwrap())
+            .finalize();
+
+        let mut settings = RenderSettings::new();
+        settings.width = 640;
+        settings.height = 480;
+        settings.gamma = Some(2.2);
+        settings.camera.zoom = 1.5;
+        settings.density_estimation = Some(DensityEstimation::new());
+        settings.palette = Some(PaletteSource::Stops(vec![
+            PaletteStop { position: 0.0, color: (1.0, 0.0, 0.0, 1.0) },
+            PaletteStop { position: 1.0, color: (0.0, 0.0, 1.0, 1.0) }
+        ]));
+
+        SystemBuilder::new()
+            .add_weighted_transform(first, 1.0)
+            .add_weighted_transform(second, 0.5)
+            .xaos(0, vec![0.0, 2.0])
+            .final_transform(final_transform)
+            .reset_transformation(AffineTransformation::from_coefficients(2.0, 0.0, -1.0, 0.0, 2.0, -1.0))
+            .ttl(150)
+            .fuse(10)
+            .bound(1e6)
+            .seed(42)
+            .render_settings(settings)
+            .finalize()
+            .unwrap()
+    }
+
+    fn bytes(message: &Message) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_message(&mut output, message).unwrap();
+
+        output
+    }
+
+    #[test]
+    fn write_read_write_gives_identical_bytes() {
+        let written = bytes(&Message::Start(system()));
+        let read = read_message(&mut &written[..]).unwrap();
+
+        assert!(written == bytes(&read));
+    }
+
+    #[test]
+    fn stop_round_trips() {
+        let written = bytes(&Message::Stop);
+
+        match read_message(&mut &written[..]).unwrap() {
+            Message::Stop => {},
+            Message::Start(_) => panic!("Expected a stop message")
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Fractal flame engine: flames are described as a `System` of weighted
+//! `Transform`s, iterated into particles by the `server` and turned into
+//! images by the `render` module. Flames are exchanged as `chaoskit.capnp`
+//! messages through `interop`.
+
+extern crate rand;
+extern crate byteorder;
+extern crate crossbeam;
+extern crate capnp;
+extern crate rustc_serialize;
+extern crate image;
+
+pub mod types;
+pub mod variations;
+pub mod interop;
+pub mod server;
+pub mod histogram;
+pub mod flam3;
+pub mod render;
+mod consts;
+
+#[allow(dead_code)]
+pub mod chaoskit_capnp {
+    include!(concat!(env!("OUT_DIR"), "/chaoskit_capnp.rs"));
+}
+
+pub use types::{Number, Point, Particle, System, Transform, Variation, AffineTransformation, RenderSettings, Message};
+pub use types::system::SystemBuilder;
+pub use types::transform::TransformBuilder;
+pub use variations::{VariationRegistry, register_variation};
+pub use interop::{read_message, write_message};
@@ -1,96 +1,19 @@
-extern crate rand;
-extern crate byteorder;
-extern crate num_cpus;
-extern crate crossbeam;
-extern crate capnp;
-
-mod types;
-mod consts;
-mod variations;
-mod interop;
-
-#[allow(dead_code)]
-mod chaoskit_capnp {
-    include!(concat!(env!("OUT_DIR"), "/chaoskit_capnp.rs"));
-}
+extern crate reactor;
 
-use types::{Particle, Message};
-use types::system::*;
-use std::sync::mpsc;
-use std::io::prelude::*;
-use std::io;
+use reactor::{interop, server, flam3, Message};
+use std::io::{self, Read, Write};
+use std::fs::File;
 use std::env;
+use std::process;
+use std::sync::Arc;
+
+const USAGE: &'static str = "Usage: reactor [--listen unix:PATH | --listen tcp:HOST:PORT] [--seed SEED] [--output particles|histogram] [--format capnp|json]
+       reactor import-flam3 FILE [--index INDEX]
+       reactor convert --to json|capnp";
 
 // use types::transform::*;
 // use types::affine_transformation::*;
 
-enum Status {
-    Generated(Vec<Particle>),
-    Finished,
-}
-
-fn generate(system: System) {
-    let mut global_rng = rand::thread_rng();
-
-    let particle_count = env::var("PARTICLE_COUNT").unwrap_or("10000".to_string()).parse::<u32>().unwrap();
-    let iteration_count = env::var("ITERATION_COUNT").unwrap_or("1000".to_string()).parse::<u32>().unwrap();
-    let particle_buffer_size = env::var("PARTICLE_BUFFER_SIZE").unwrap_or("1000".to_string()).parse::<usize>().unwrap();
-    let channel_size = env::var("CHANNEL_SIZE").unwrap_or("10".to_string()).parse::<usize>().unwrap();
-
-    let thread_count = num_cpus::get();
-    let chunk_size = ((particle_count as f32) / (thread_count as f32)).ceil() as usize;
-    let mut particles: Vec<Particle> = (0..particle_count).map(|_| system.make_particle(&mut global_rng)).collect();
-
-    crossbeam::scope(|scope| {
-        let (tx, rx) = mpsc::sync_channel(channel_size);
-
-        for particle_chunk in particles.chunks_mut(chunk_size) {
-            let (tx, system) = (tx.clone(), &system);
-
-            scope.spawn(move|| {
-                let mut rng = rand::thread_rng();
-
-                let mut buffer = Vec::with_capacity(particle_buffer_size);
-
-                for _ in 0..iteration_count {
-                    for particle in particle_chunk.iter_mut() {
-                        let projected_particle = system.step(particle, &mut rng);
-
-                        if buffer.len() < particle_buffer_size {
-                            buffer.push(projected_particle);
-                        } else {
-                            tx.send(Status::Generated(buffer.clone())).unwrap();
-                            buffer.clear();
-                        }
-                    }
-                }
-
-                if buffer.len() > 0 {
-                    tx.send(Status::Generated(buffer)).unwrap();
-                }
-
-                tx.send(Status::Finished).unwrap();
-            });
-        }
-
-        let stdout = io::stdout();
-        let mut writer = io::BufWriter::new(stdout);
-        let mut finished_threads: usize = 0;
-        while finished_threads < thread_count {
-            let message = rx.recv().unwrap();
-
-            match message {
-                Status::Generated(buffer) => {
-                    for particle in buffer.iter() {
-                        let _ = writer.write(&particle.bytes());
-                    }
-                },
-                Status::Finished => finished_threads += 1
-            }
-        }
-    });
-}
-
 fn main() {
     // let variation = variations::DeJong(1.6623940085992217,-0.6880100890994072,1.4784153904765844,1.7967103328555822);
     // let transform = TransformBuilder::new()
@@ -107,18 +30,163 @@ fn main() {
     // println!("{:#?}", system);
     // generate(system);
 
-    let stdin = io::stdin();
-    let mut reader = io::BufReader::new(stdin);
+    if env::args().nth(1).map_or(false, |command| command == "import-flam3") {
+        return import_flam3(env::args().skip(2).collect());
+    }
+
+    if env::args().nth(1).map_or(false, |command| command == "convert") {
+        return convert(env::args().skip(2).collect());
+    }
 
-    match interop::read_message(&mut reader) {
-        Ok(message) => match message {
-            Message::Start(system) => {
-                generate(system);
+    let mut config = server::Config::from_env();
+    let mut address = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--listen" => {
+                address = match args.next().map(|value| server::Address::parse(&value)) {
+                    Some(Ok(address)) => Some(address),
+                    Some(Err(e)) => fail(e),
+                    None => fail(USAGE)
+                };
+            },
+            "--output" => {
+                config.output = match args.next().map(|value| server::Output::parse(&value)) {
+                    Some(Ok(output)) => output,
+                    Some(Err(e)) => fail(e),
+                    None => fail(USAGE)
+                };
+            },
+            "--format" => {
+                config.format = match args.next().map(|value| interop::Format::parse(&value)) {
+                    Some(Ok(format)) => format,
+                    Some(Err(e)) => fail(e),
+                    None => fail(USAGE)
+                };
+            },
+            "--seed" => {
+                config.seed = match args.next().map(|value| value.parse::<u64>()) {
+                    Some(Ok(seed)) => Some(seed),
+                    Some(Err(_)) => fail("Seed must be an unsigned integer"),
+                    None => fail(USAGE)
+                };
             },
-            _ => {}
+            _ => fail(USAGE)
+        }
+    }
+
+    let reporter: server::Reporter = Arc::new(print_report);
+
+    match address {
+        Some(address) => {
+            if let Err(e) = server::listen(&address, &config, &reporter) {
+                fail(&format!("{}", e));
+            }
+        },
+        None => {
+            let writer = io::BufWriter::new(io::stdout());
+
+            server::serve(io::stdin(), writer, config, reporter);
+        }
+    }
+}
+
+/// Writes a single line of `key=value` pairs to stderr, so schedulers can
+/// parse it.
+fn print_report(report: &server::Report) {
+    let statistics = &report.statistics;
+    let seconds = (report.elapsed.as_secs() as f64) + (report.elapsed.subsec_nanos() as f64) / 1e9;
+    let rate = if seconds > 0.0 { (statistics.iterations as f64) / seconds } else { 0.0 };
+    let eta = if rate > 0.0 { (report.total_iterations.saturating_sub(statistics.iterations) as f64) / rate } else { 0.0 };
+    let hits: Vec<String> = statistics.transform_hits.iter().map(|hits| hits.to_string()).collect();
+    let label = if report.finished { "done" } else { "progress" };
+
+    let _ = writeln!(io::stderr(), "{} iterations={}/{} elapsed={:.1}s particles_per_second={:.0} eta={:.1}s resets={} bad_values={} transform_hits={}",
+        label, statistics.iterations, report.total_iterations, seconds, rate, eta, statistics.resets, statistics.bad_values, hits.join(","));
+}
+
+/// Writes the flames from a flam3 file to stdout as `start` messages, either
+/// all of them or just the one at the given index.
+fn import_flam3(args: Vec<String>) {
+    let (path, index) = match args.len() {
+        1 => (&args[0], None),
+        3 if args[1] == "--index" => match args[2].parse::<usize>() {
+            Ok(index) => (&args[0], Some(index)),
+            Err(_) => fail("Index must be an unsigned integer")
         },
-        Err(e) => {
-            println!("Error: {}", e);
+        _ => fail(USAGE)
+    };
+
+    let mut input = String::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut input)) {
+        fail(&format!("{}", e));
+    }
+
+    let conversions = match flam3::convert(&input) {
+        Ok(conversions) => conversions,
+        Err(e) => fail(&format!("Error: {}", e))
+    };
+
+    let stdout = io::stdout();
+    let mut writer = io::BufWriter::new(stdout.lock());
+
+    for (i, conversion) in conversions.into_iter().enumerate() {
+        if index.map_or(false, |index| index != i) {
+            continue;
+        }
+
+        for name in conversion.unsupported.iter() {
+            let _ = writeln!(io::stderr(), "Flame {} \"{}\": unsupported variation or attribute {}", i, conversion.name, name);
         }
+
+        // Flames that can't be converted are skipped, unless they were asked for
+        let system = match conversion.system {
+            Ok(system) => system,
+            Err(e) => {
+                let message = format!("Flame {} \"{}\": {}", i, conversion.name, e);
+
+                if index.is_some() {
+                    fail(&message);
+                }
+
+                let _ = writeln!(io::stderr(), "{}", message);
+                continue;
+            }
+        };
+
+        if let Err(e) = interop::write_message(&mut writer, &Message::Start(system)) {
+            fail(&format!("{}", e));
+        }
+    }
+}
+
+/// Converts messages from stdin into the given format on stdout, reading
+/// them in the other one.
+fn convert(args: Vec<String>) {
+    let format = match args.len() {
+        2 if args[0] == "--to" => match interop::Format::parse(&args[1]) {
+            Ok(format) => format,
+            Err(e) => fail(e)
+        },
+        _ => fail(USAGE)
+    };
+
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    let mut reader = io::BufReader::new(stdin.lock());
+    let mut writer = io::BufWriter::new(stdout.lock());
+
+    let result = match format {
+        interop::Format::Json => interop::json::capnp_to_json(&mut reader, &mut writer),
+        interop::Format::Capnp => interop::json::json_to_capnp(&mut reader, &mut writer)
+    };
+
+    if let Err(e) = result.and_then(|_| writer.flush().map_err(interop::Error::from)) {
+        fail(&format!("Error: {}", e));
     }
 }
+
+fn fail(message: &str) -> ! {
+    let _ = writeln!(io::stderr(), "{}", message);
+    process::exit(1)
+}
@@ -1,222 +1,367 @@
-extern crate byteorder;
+extern crate reactor;
 extern crate image;
 
-use std::io::{self, Read, BufReader};
-use std::ops;
+use std::io::{self, BufReader, Read, Write};
+use std::env;
+use std::fs::File;
 use std::path::Path;
-use std::collections::VecDeque;
-use std::convert::From;
-use image::Rgba;
-use byteorder::{ByteOrder, BigEndian};
-
-struct Point {
-    x: f64,
-    y: f64,
-    color: f64
+use std::process;
+use std::str::FromStr;
+use std::f64;
+use reactor::{interop, render, Message, Particle, Point, RenderSettings};
+use reactor::types::render_settings::{Camera, DensityEstimation, PaletteSource};
+use reactor::render::{Filter, ImagePalette, Renderer};
+
+const USAGE: &'static str = "Usage: reactor-client [--input FILE] [--input-format particles|histogram] [--flame FILE]
+                      [--output FILE] [--format png|jpeg|ppm] [--palette FILE]
+                      [--width WIDTH] [--height HEIGHT] [--gamma GAMMA] [--exposure STOPS]
+                      [--supersample FACTOR] [--filter box|gaussian|mitchell]
+                      [--center X,Y] [--zoom ZOOM] [--rotation DEGREES] [--auto-fit]
+                      [--estimator-radius RADIUS] [--estimator-minimum RADIUS] [--estimator-curve CURVE]";
+
+// Auto-fit frames the first particles of the stream, ignoring the outermost
+// ones on every side and leaving a margin around the rest
+const AUTO_FIT_SAMPLES: usize = 100000;
+const AUTO_FIT_PERCENTILE: f64 = 0.01;
+const AUTO_FIT_MARGIN: f64 = 0.05;
+
+/// What the reactor was asked to send.
+enum InputFormat {
+    Particles,
+    Histogram
 }
 
-struct ProjectedPoint<'a> {
-    x: f64,
-    y: f64,
-    color: f64,
-    extent: &'a Extent
+struct Options {
+    /// Read from stdin if not set.
+    input: Option<String>,
+    input_format: InputFormat,
+    flame: Option<String>,
+    output: String,
+    /// Guessed from the output's extension if not set.
+    format: Option<image::ImageFormat>,
+    palette: Option<String>,
+    width: Option<usize>,
+    height: Option<usize>,
+    gamma: Option<f64>,
+    exposure: Option<f64>,
+    supersample: usize,
+    filter: Filter,
+    center: Option<Point>,
+    zoom: Option<f64>,
+    /// In degrees, counterclockwise.
+    rotation: Option<f64>,
+    auto_fit: bool,
+    estimator_radius: Option<f64>,
+    estimator_minimum: Option<f64>,
+    estimator_curve: Option<f64>
 }
 
-type Pixel = usize;
-
-#[derive(Clone, Copy)]
-struct Color {
-    r: f64,
-    g: f64,
-    b: f64,
-    a: f64
-}
+fn parse_options() -> Options {
+    let mut options = Options {
+        input: None,
+        input_format: InputFormat::Particles,
+        flame: None,
+        output: "output.png".to_string(),
+        format: None,
+        palette: None,
+        width: None,
+        height: None,
+        gamma: None,
+        exposure: None,
+        supersample: 1,
+        filter: Filter::Box,
+        center: None,
+        zoom: None,
+        rotation: None,
+        auto_fit: false,
+        estimator_radius: None,
+        estimator_minimum: None,
+        estimator_curve: None
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--input" => options.input = Some(next_arg(&mut args)),
+            "--input-format" => {
+                options.input_format = match &next_arg(&mut args)[..] {
+                    "particles" => InputFormat::Particles,
+                    "histogram" => InputFormat::Histogram,
+                    _ => fail("Input format must be either particles or histogram")
+                };
+            },
+            "--flame" => options.flame = Some(next_arg(&mut args)),
+            "--output" => options.output = next_arg(&mut args),
+            "--format" => {
+                options.format = match parse_format(&next_arg(&mut args)) {
+                    Some(format) => Some(format),
+                    None => fail("Format must be either png, jpeg or ppm")
+                };
+            },
+            "--palette" => options.palette = Some(next_arg(&mut args)),
+            "--width" => options.width = Some(parse_arg(&mut args, "Width must be an unsigned integer")),
+            "--height" => options.height = Some(parse_arg(&mut args, "Height must be an unsigned integer")),
+            "--gamma" => options.gamma = Some(parse_arg(&mut args, "Gamma must be a number")),
+            "--exposure" => options.exposure = Some(parse_arg(&mut args, "Exposure must be a number")),
+            "--supersample" => {
+                options.supersample = parse_arg(&mut args, "Supersampling factor must be a positive integer");
+
+                if options.supersample == 0 {
+                    fail("Supersampling factor must be a positive integer");
+                }
+            },
+            "--filter" => {
+                options.filter = match Filter::parse(&next_arg(&mut args)) {
+                    Ok(filter) => filter,
+                    Err(e) => fail(e)
+                };
+            },
+            "--center" => {
+                let center = next_arg(&mut args);
+                let coordinates: Vec<Result<f64, _>> = center.split(',').map(|value| value.trim().parse::<f64>()).collect();
+
+                options.center = match (coordinates.len(), coordinates.get(0), coordinates.get(1)) {
+                    (2, Some(&Ok(x)), Some(&Ok(y))) => Some(Point::from_xy(x, y)),
+                    _ => fail("Center must be given as X,Y")
+                };
+            },
+            "--zoom" => {
+                let zoom: f64 = parse_arg(&mut args, "Zoom must be a positive number");
+
+                if !(zoom > 0.0) {
+                    fail("Zoom must be a positive number");
+                }
 
-impl Point {
-    fn from_bytes(bytes: &[u8]) -> Point {
-        Point {
-            x: BigEndian::read_f64(&bytes[8*0..8*1]),
-            y: BigEndian::read_f64(&bytes[8*1..8*2]),
-            color: BigEndian::read_f64(&bytes[8*2..8*3])
+                options.zoom = Some(zoom);
+            },
+            "--rotation" => options.rotation = Some(parse_arg(&mut args, "Rotation must be a number")),
+            "--auto-fit" => options.auto_fit = true,
+            "--estimator-radius" => options.estimator_radius = Some(parse_arg(&mut args, "Estimator radius must be a number")),
+            "--estimator-minimum" => options.estimator_minimum = Some(parse_arg(&mut args, "Estimator minimum must be a number")),
+            "--estimator-curve" => options.estimator_curve = Some(parse_arg(&mut args, "Estimator curve must be a number")),
+            _ => fail(USAGE)
         }
     }
 
-    fn project<'a>(&'a self, extent: &'a Extent) -> ProjectedPoint {
-        ProjectedPoint {
-            x: (self.x + 2.0) / 4.0 * (extent.x as f64),
-            y: (self.y + 2.0) / 4.0 * (extent.y as f64),
-            color: self.color,
-            extent: extent
-        }
-    }
+    options
 }
 
-impl<'a> ProjectedPoint<'a> {
-    fn does_fit(&self) -> bool {
-        self.x >= 0.0 && self.y >= 0.0 && self.x < (self.extent.x as f64) && self.y < (self.extent.y as f64)
+fn next_arg<I: Iterator<Item=String>>(args: &mut I) -> String {
+    match args.next() {
+        Some(value) => value,
+        None => fail(USAGE)
     }
+}
 
-    fn to_pixel(&self) -> Pixel {
-        // Pixel { x: self.x as usize, y: self.y as usize }
-        (self.y.trunc() as usize) * self.extent.x + (self.x.trunc() as usize)
+fn parse_arg<T: FromStr, I: Iterator<Item=String>>(args: &mut I, message: &str) -> T {
+    match next_arg(args).parse::<T>() {
+        Ok(value) => value,
+        Err(_) => fail(message)
     }
 }
 
-impl Color {
-    fn new() -> Color {
-        Color {r: 0.0, g: 0.0, b: 0.0, a: 0.0}
+fn parse_format(format: &str) -> Option<image::ImageFormat> {
+    match format {
+        "png" => Some(image::ImageFormat::PNG),
+        "jpeg" | "jpg" => Some(image::ImageFormat::JPEG),
+        "ppm" => Some(image::ImageFormat::PPM),
+        _ => None
     }
+}
 
-    fn map(&self, exposure: f64, gamma: f64) -> Vec<u8> {
-        if self.a == 0.0 {
-            return vec![0u8; 3];
+/// Reads up to `count` particles from the start of the stream.
+fn read_sample<R: Read>(reader: &mut R, count: usize) -> io::Result<Vec<Particle>> {
+    let mut bytes: [u8; 8*3] = [0; 8 * 3];
+    let mut particles = Vec::with_capacity(count);
+
+    while particles.len() < count {
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => particles.push(Particle::from_bytes(&bytes)),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e)
         }
+    }
+
+    Ok(particles)
+}
 
-        let scale = self.a.log2() / self.a;
-        let inverted_gamma = 1.0 / gamma;
+/// Takes the render settings from the flame stored at `path`.
+fn read_render_settings(path: &Path) -> Result<RenderSettings, interop::Error> {
+    let file = try!(File::open(path).map_err(|e| interop::Error::from(format!("{}: {}", path.display(), e))));
+    let mut reader = BufReader::new(file);
 
-        vec![
-            ((tone_map(self.r * scale) * exposure).powf(inverted_gamma) * 255.0).min(255.0).trunc() as u8,
-            ((tone_map(self.g * scale) * exposure).powf(inverted_gamma) * 255.0).min(255.0).trunc() as u8,
-            ((tone_map(self.b * scale) * exposure).powf(inverted_gamma) * 255.0).min(255.0).trunc() as u8
-        ]
+    match try!(interop::read_message(&mut reader)) {
+        Message::Start(system) => Ok(system.render_settings),
+        Message::Stop => Ok(RenderSettings::new())
     }
 }
 
-impl<'a> ops::Add for &'a Color {
-    type Output = Color;
+fn main() {
+    let options = parse_options();
 
-    fn add(self, rhs: &Color) -> Color {
-        Color {
-            r: self.r + rhs.r,
-            g: self.g + rhs.g,
-            b: self.b + rhs.b,
-            a: self.a + rhs.a
+    // Histogram frames are already sized and framed by the reactor
+    if let InputFormat::Histogram = options.input_format {
+        if options.width.is_some() || options.height.is_some() || options.supersample > 1 {
+            fail("Width, height and supersampling can't be changed for histogram input, set them in the flame instead");
         }
-    }
-}
 
-impl<'a> From<&'a Rgba<u8>> for Color {
-    fn from(rgba: &'a Rgba<u8>) -> Color {
-        Color {
-            r: (rgba[0] as f64) / 255.0,
-            g: (rgba[1] as f64) / 255.0,
-            b: (rgba[2] as f64) / 255.0,
-            a: (rgba[3] as f64) / 255.0
+        if options.center.is_some() || options.zoom.is_some() || options.rotation.is_some() {
+            fail("Center, zoom and rotation can't be changed for histogram input, set them in the flame instead");
         }
     }
-}
-
-struct Extent {
-    x: usize,
-    y: usize
-}
 
-trait Palette {
-    fn color_at(&self, point: f64) -> Color;
-}
+    let mut settings = match options.flame {
+        Some(ref path) => match read_render_settings(&Path::new(path)) {
+            Ok(settings) => settings,
+            Err(e) => fail(&format!("Error: {}", e))
+        },
+        None => RenderSettings::new()
+    };
 
-struct ImagePalette {
-    colors: Vec<Color>
-}
+    if let Some(width) = options.width {
+        settings.width = width;
+    }
 
-impl ImagePalette {
-    fn from_file(path: &Path) -> Result<ImagePalette, image::ImageError> {
-        let img = try!(image::open(path)).to_rgba();
-        let (width, _) = img.dimensions();
-        let mut colors = Vec::with_capacity(width as usize);
+    if let Some(height) = options.height {
+        settings.height = height;
+    }
 
-        for x in 0..width {
-            let pixel = img.get_pixel(x, 0);
-            colors.push(Color::from(pixel));
-        }
+    if options.gamma.is_some() {
+        settings.gamma = options.gamma;
+    }
 
-        Ok(ImagePalette { colors: colors })
+    if options.exposure.is_some() {
+        settings.exposure = options.exposure;
     }
-}
 
-impl Palette for ImagePalette {
-    fn color_at(&self, point: f64) -> Color {
-        let index = (point * (self.colors.len() - 1) as f64).round() as usize;
+    // Any of the estimator options turns density estimation on, a radius of 0 turns it off
+    if options.estimator_radius.is_some() || options.estimator_minimum.is_some() || options.estimator_curve.is_some() {
+        let mut estimation = settings.density_estimation.unwrap_or_else(DensityEstimation::new);
 
-        self.colors[index]
-    }
-}
+        if let Some(radius) = options.estimator_radius {
+            estimation.max_radius = radius;
+        }
 
-fn tone_map(subpixel: f64) -> f64 {
-    subpixel / (subpixel + 1.0)
-}
+        if let Some(minimum) = options.estimator_minimum {
+            estimation.min_radius = minimum;
+        }
 
-fn magic_model_fn(x: f64, a: f64, b: f64) -> f64 {
-    (1.0 - a).powf(x.powf(b))
-}
+        if let Some(curve) = options.estimator_curve {
+            estimation.curve = curve;
+        }
 
-fn make_image(colors: Vec<Color>, particle_count: i32) -> Vec<u8> {
-    let exposure_f = magic_model_fn(particle_count as f64, 0.0796457, 0.153342);
-    let gamma = magic_model_fn(particle_count as f64, 0.0999924, 0.178006);
+        settings.density_estimation = if estimation.max_radius > 0.0 { Some(estimation) } else { None };
+    }
 
-    println!("Exposure: {:.*}, Gamma: {:.*}", 6, exposure_f, 6, gamma);
+    if let Some(ref path) = options.palette {
+        settings.palette = Some(PaletteSource::File(path.clone()));
+    }
 
-    let exposure = (2.0f64).powf(exposure_f);
+    // Histogram frames come with their colors already looked up
+    let palette = match options.input_format {
+        InputFormat::Histogram => ImagePalette::from_stops(&[]),
+        InputFormat::Particles => match ImagePalette::from_settings(&settings) {
+            Ok(palette) => palette,
+            Err(e) => fail(&format!("Error: {}", e))
+        }
+    };
 
-    colors.iter().flat_map(move|ref color| color.map(exposure, gamma).into_iter()).collect()
-}
+    let mut reader: Box<Read> = match options.input {
+        Some(ref path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => fail(&format!("Error: {}: {}", path, e))
+        },
+        None => Box::new(BufReader::new(io::stdin()))
+    };
 
-const IMAGE_SIZE: Extent = Extent { x: 1024, y: 1024 };
-const PIXEL_COUNT: usize = IMAGE_SIZE.x * IMAGE_SIZE.y;
+    println!("Capturing points…");
 
-fn main() {
-    let mut float_buffer: Vec<Color> = vec![Color::new(); PIXEL_COUNT];
-    let mut point_bytes: [u8; 24] = [0; 24];
+    let sample = if options.auto_fit {
+        if let InputFormat::Histogram = options.input_format {
+            fail("Auto-fit needs particles, histogram frames are already framed by the reactor");
+        }
 
-    let mut reader = BufReader::new(io::stdin());
-    // let mut file = File::open("testdata.bin").ok().unwrap();
-    // let mut reader = BufReader::new(file);
+        match read_sample(&mut reader, AUTO_FIT_SAMPLES) {
+            Ok(sample) => sample,
+            Err(e) => fail(&format!("Error: {}", e))
+        }
+    } else {
+        Vec::new()
+    };
 
-    let palette = ImagePalette::from_file(&Path::new("palette.png")).unwrap();
+    if options.auto_fit {
+        let points: Vec<Point> = sample.iter().map(|particle| particle.point).collect();
+        settings.camera = Camera::fit(&points, settings.width, settings.height, AUTO_FIT_PERCENTILE, AUTO_FIT_MARGIN);
 
-    println!("Capturing points…");
+        println!("Camera: center {:.*}, {:.*}, zoom {:.*}", 6, settings.camera.center.x, 6, settings.camera.center.y, 6, settings.camera.zoom);
+    }
 
-    let mut fit_count: i32 = 0;
-    let mut total_count: i32 = 0;
-    let mut point_buffer = VecDeque::with_capacity(24);
+    // Applied after fitting, so it can be adjusted
+    if let Some(center) = options.center {
+        settings.camera.center = center;
+    }
 
-    loop {
-        match reader.read(&mut point_bytes) {
-            Ok(count) => {
-                for i in 0..count {
-                    point_buffer.push_back(point_bytes[i]);
-                }
+    if let Some(zoom) = options.zoom {
+        settings.camera.zoom = zoom;
+    }
 
-                if point_buffer.is_empty() || (count == 0 && point_buffer.len() < 24) { break; }
-                else if point_buffer.len() < 24 { continue; }
+    if let Some(rotation) = options.rotation {
+        settings.camera.rotation = rotation * f64::consts::PI / 180.0;
+    }
 
-                let mut bytes = Vec::with_capacity(24);
-                for _ in 0..24 {
-                    bytes.push(point_buffer.pop_front().unwrap());
-                }
+    // Accumulated at a multiple of the image size and scaled down at the end
+    let factor = options.supersample;
+    let mut accumulation_settings = settings.clone();
+    accumulation_settings.width *= factor;
+    accumulation_settings.height *= factor;
 
-                total_count += 1;
+    let mut renderer = Renderer::new(accumulation_settings, palette);
 
-                let point = Point::from_bytes(&bytes);
-                let image_size = &IMAGE_SIZE;
-                let projected_point = point.project(image_size);
+    for particle in sample.iter() {
+        renderer.add(particle);
+    }
 
-                if projected_point.does_fit() {
-                    fit_count += 1;
+    let result = match options.input_format {
+        InputFormat::Histogram => renderer.read_histogram(&mut reader),
+        InputFormat::Particles => renderer.read_particles(&mut reader)
+    };
 
-                    let pixel = projected_point.to_pixel();
-                    float_buffer[pixel] = &float_buffer[pixel] + &palette.color_at(projected_point.color);
-                }
-            }
-            Err(err) => panic!("{}", err)
-        }
+    if let Err(e) = result {
+        fail(&format!("Error: {}", e));
     }
 
-    println!("{} points captured, {} fit", total_count, fit_count);
+    println!("{} points fit", renderer.count());
     println!("Creating image…");
 
-    let byte_buffer = make_image(float_buffer, fit_count);
+    let (exposure, gamma) = render::exposure_and_gamma(renderer.count(), &settings);
+    println!("Exposure: {:.*}, Gamma: {:.*}", 6, exposure, 6, gamma);
+
+    // Histogram frames come in whatever size the reactor used
+    let (width, height) = (renderer.width() / factor, renderer.height() / factor);
+    let colors = render::downsample(renderer.colors(), renderer.width(), renderer.height(), factor, options.filter);
+
+    settings.width = width;
+    settings.height = height;
+    let byte_buffer = render::make_image(&colors, renderer.count(), &settings);
+
+    let format = options.format.unwrap_or_else(|| {
+        Path::new(&options.output).extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(parse_format)
+            .unwrap_or(image::ImageFormat::PNG)
+    });
+
+    let buffer = image::ImageBuffer::from_raw(width as u32, height as u32, byte_buffer).unwrap();
+    let result = File::create(&options.output)
+        .map_err(image::ImageError::from)
+        .and_then(|mut file| image::DynamicImage::ImageRgb8(buffer).save(&mut file, format));
+
+    if let Err(e) = result {
+        fail(&format!("Error: {}", e));
+    }
+}
 
-    image::save_buffer(&Path::new("output.png"), &byte_buffer[..], IMAGE_SIZE.x as u32, IMAGE_SIZE.y as u32, image::RGB(8)).unwrap()
+fn fail(message: &str) -> ! {
+    let _ = writeln!(io::stderr(), "{}", message);
+    process::exit(1)
 }
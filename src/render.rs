@@ -0,0 +1,524 @@
+//! Turns particles or histogram frames into images, using the palette and
+//! tone mapping settings of a flame's `RenderSettings`. `Renderer` does the
+//! whole job, the pieces it's made of are available on their own.
+
+use std::io::{self, Read};
+use std::ops;
+use std::path::Path;
+use std::convert::From;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use image;
+use image::Rgba;
+use rand::Rng;
+use byteorder::{ByteOrder, BigEndian};
+use types::{Particle, System, RenderSettings};
+use types::render_settings::{DensityEstimation, PaletteSource, PaletteStop};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64
+}
+
+impl Color {
+    pub fn new() -> Color {
+        Color {r: 0.0, g: 0.0, b: 0.0, a: 0.0}
+    }
+
+    /// Tone maps an accumulated color into 8-bit RGB, blending it over the
+    /// background according to how much it covers.
+    pub fn map(&self, exposure: f64, gamma: f64, background: (f64, f64, f64)) -> Vec<u8> {
+        let (br, bg, bb) = background;
+
+        if self.a == 0.0 {
+            return vec![to_byte(br), to_byte(bg), to_byte(bb)];
+        }
+
+        let scale = self.a.log2() / self.a;
+        let inverted_gamma = 1.0 / gamma;
+
+        let map_subpixel = |subpixel: f64| (tone_map(subpixel * scale) * exposure).powf(inverted_gamma);
+        let coverage = map_subpixel(self.a).min(1.0);
+
+        vec![
+            to_byte(map_subpixel(self.r) + br * (1.0 - coverage)),
+            to_byte(map_subpixel(self.g) + bg * (1.0 - coverage)),
+            to_byte(map_subpixel(self.b) + bb * (1.0 - coverage))
+        ]
+    }
+}
+
+impl<'a> ops::Add for &'a Color {
+    type Output = Color;
+
+    fn add(self, rhs: &Color) -> Color {
+        Color {
+            r: self.r + rhs.r,
+            g: self.g + rhs.g,
+            b: self.b + rhs.b,
+            a: self.a + rhs.a
+        }
+    }
+}
+
+impl<'a> ops::Mul<f64> for &'a Color {
+    type Output = Color;
+
+    fn mul(self, rhs: f64) -> Color {
+        Color {
+            r: self.r * rhs,
+            g: self.g * rhs,
+            b: self.b * rhs,
+            a: self.a * rhs
+        }
+    }
+}
+
+impl<'a> From<&'a Rgba<u8>> for Color {
+    fn from(rgba: &'a Rgba<u8>) -> Color {
+        Color {
+            r: (rgba[0] as f64) / 255.0,
+            g: (rgba[1] as f64) / 255.0,
+            b: (rgba[2] as f64) / 255.0,
+            a: (rgba[3] as f64) / 255.0
+        }
+    }
+}
+
+pub trait Palette {
+    fn color_at(&self, point: f64) -> Color;
+}
+
+pub struct ImagePalette {
+    colors: Vec<Color>
+}
+
+const STOP_PALETTE_SIZE: usize = 256;
+
+impl ImagePalette {
+    pub fn from_source(source: &PaletteSource) -> Result<ImagePalette, image::ImageError> {
+        match *source {
+            PaletteSource::File(ref path) => ImagePalette::from_file(&Path::new(path)),
+            PaletteSource::Stops(ref stops) => Ok(ImagePalette::from_stops(stops)),
+            PaletteSource::Image(ref data) => ImagePalette::from_image(try!(image::load_from_memory(data)))
+        }
+    }
+
+    /// The palette of the render settings, or `palette.png` from the current
+    /// directory if they don't have one.
+    pub fn from_settings(settings: &RenderSettings) -> Result<ImagePalette, image::ImageError> {
+        match settings.palette {
+            Some(ref source) => ImagePalette::from_source(source),
+            None => ImagePalette::from_file(&Path::new("palette.png"))
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<ImagePalette, image::ImageError> {
+        ImagePalette::from_image(try!(image::open(path)))
+    }
+
+    /// Takes the colors from the first row of the image.
+    pub fn from_image(image: image::DynamicImage) -> Result<ImagePalette, image::ImageError> {
+        let img = image.to_rgba();
+        let (width, _) = img.dimensions();
+        let mut colors = Vec::with_capacity(width as usize);
+
+        for x in 0..width {
+            let pixel = img.get_pixel(x, 0);
+            colors.push(Color::from(pixel));
+        }
+
+        Ok(ImagePalette { colors: colors })
+    }
+
+    /// Samples colors linearly interpolated between the stops, which don't
+    /// have to be sorted.
+    pub fn from_stops(stops: &[PaletteStop]) -> ImagePalette {
+        let mut sorted: Vec<(f64, Color)> = stops.iter().map(|stop| {
+            let (r, g, b, a) = stop.color;
+            (stop.position, Color { r: r, g: g, b: b, a: a })
+        }).collect();
+        sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        if sorted.is_empty() {
+            return ImagePalette { colors: vec![Color::new()] };
+        }
+
+        let colors = (0..STOP_PALETTE_SIZE).map(|i| {
+            let position = (i as f64) / ((STOP_PALETTE_SIZE - 1) as f64);
+
+            match sorted.iter().position(|stop| stop.0 > position) {
+                Some(0) => sorted[0].1,
+                None => sorted[sorted.len() - 1].1,
+                Some(next) => {
+                    let (from, to) = (sorted[next - 1], sorted[next]);
+                    let t = (position - from.0) / (to.0 - from.0);
+
+                    &(&from.1 * (1.0 - t)) + &(&to.1 * t)
+                }
+            }
+        }).collect();
+
+        ImagePalette { colors: colors }
+    }
+}
+
+impl Palette for ImagePalette {
+    fn color_at(&self, point: f64) -> Color {
+        // Also 0 for NaN
+        let point = point.max(0.0).min(1.0);
+        let index = (point * (self.colors.len() - 1) as f64).round() as usize;
+
+        self.colors[index]
+    }
+}
+
+fn tone_map(subpixel: f64) -> f64 {
+    subpixel / (subpixel + 1.0)
+}
+
+fn to_byte(subpixel: f64) -> u8 {
+    (subpixel * 255.0).max(0.0).min(255.0).trunc() as u8
+}
+
+fn magic_model_fn(x: f64, a: f64, b: f64) -> f64 {
+    (1.0 - a).powf(x.powf(b))
+}
+
+/// The exposure (in stops) and gamma used for an image of that many
+/// particles, taken from the settings unless they're automatic.
+pub fn exposure_and_gamma(particle_count: u64, settings: &RenderSettings) -> (f64, f64) {
+    (
+        settings.exposure.unwrap_or_else(|| magic_model_fn(particle_count as f64, 0.0796457, 0.153342)),
+        settings.gamma.unwrap_or_else(|| magic_model_fn(particle_count as f64, 0.0999924, 0.178006))
+    )
+}
+
+/// Spreads every accumulated pixel over a kernel that gets narrower the
+/// denser the pixel is, see `DensityEstimation`. The alpha of a pixel is
+/// taken as its density.
+pub fn estimate_density(colors: &[Color], width: usize, height: usize, estimation: &DensityEstimation) -> Vec<Color> {
+    let mut estimated = vec![Color::new(); colors.len()];
+    let mut kernels: HashMap<usize, Vec<(isize, isize, f64)>> = HashMap::new();
+    let max_radius = estimation.max_radius.max(estimation.min_radius);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = &colors[y * width + x];
+            if color.a <= 0.0 {
+                continue;
+            }
+
+            // Radii within a tenth of a pixel share their kernel
+            let key = (estimation.radius(color.a).min(max_radius) * 10.0).round() as usize;
+            let kernel = kernels.entry(key).or_insert_with(|| density_kernel((key as f64) / 10.0));
+
+            for &(dx, dy, weight) in kernel.iter() {
+                let (kx, ky) = ((x as isize) + dx, (y as isize) + dy);
+
+                if kx >= 0 && ky >= 0 && (kx as usize) < width && (ky as usize) < height {
+                    let pixel = (ky as usize) * width + (kx as usize);
+                    estimated[pixel] = &estimated[pixel] + &(color * weight);
+                }
+            }
+        }
+    }
+
+    estimated
+}
+
+/// Offsets and weights of an Epanechnikov kernel, with the weights adding up
+/// to 1 so the density stays the same.
+fn density_kernel(radius: f64) -> Vec<(isize, isize, f64)> {
+    if radius < 1.0 {
+        return vec![(0, 0, 1.0)];
+    }
+
+    let extent = radius.floor() as isize;
+    let mut kernel = Vec::new();
+
+    for dy in -extent..(extent + 1) {
+        for dx in -extent..(extent + 1) {
+            let distance = ((dx * dx + dy * dy) as f64) / (radius * radius);
+
+            if distance < 1.0 {
+                kernel.push((dx, dy, 1.0 - distance));
+            }
+        }
+    }
+
+    let total = kernel.iter().fold(0.0, |sum, &(_, _, weight)| sum + weight);
+    kernel.into_iter().map(|(dx, dy, weight)| (dx, dy, weight / total)).collect()
+}
+
+/// Tone maps accumulated colors into 8-bit RGB pixels, after density
+/// estimation if the settings ask for it. There have to be `settings.width`
+/// times `settings.height` colors.
+pub fn make_image(colors: &[Color], particle_count: u64, settings: &RenderSettings) -> Vec<u8> {
+    let estimated = settings.density_estimation.map(|estimation| estimate_density(colors, settings.width, settings.height, &estimation));
+    let colors = estimated.as_ref().map_or(colors, |estimated| &estimated[..]);
+
+    let (exposure_f, gamma) = exposure_and_gamma(particle_count, settings);
+    let background = settings.background;
+
+    let exposure = (2.0f64).powf(exposure_f);
+
+    colors.iter().flat_map(move|ref color| color.map(exposure, gamma, background).into_iter()).collect()
+}
+
+/// Reconstruction filter used when scaling supersampled images down.
+#[derive(Copy, Clone, Debug)]
+pub enum Filter {
+    /// Sums every block of accumulated pixels into one, sharp but aliased.
+    Box,
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3, sharper than the Gaussian.
+    Mitchell
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Filter, &'static str> {
+        match filter {
+            "box" => Ok(Filter::Box),
+            "gaussian" => Ok(Filter::Gaussian),
+            "mitchell" => Ok(Filter::Mitchell),
+            _ => Err("Filter must be either box, gaussian or mitchell")
+        }
+    }
+
+    /// How far from a pixel's center accumulated pixels count, in pixels of
+    /// the final image.
+    fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box => 0.5,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0
+        }
+    }
+
+    fn weight(&self, x: f64) -> f64 {
+        let x = x.abs();
+
+        match *self {
+            Filter::Box => if x < 0.5 { 1.0 } else { 0.0 },
+            // Standard deviation of half a pixel, cut off at three of them
+            Filter::Gaussian => if x < 1.5 { (-2.0 * x * x).exp() } else { 0.0 },
+            Filter::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)) / 6.0
+                } else if x < 2.0 {
+                    ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)) / 6.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// First accumulated pixel and the weights of the ones from there on, for
+    /// every pixel of the final image along one axis. The weights add up to
+    /// `factor`, so the total density stays the same.
+    fn weights(&self, size: usize, factor: usize) -> Vec<(usize, Vec<f64>)> {
+        let scale = factor as f64;
+        let radius = self.radius() * scale;
+
+        (0..(size / factor)).map(|i| {
+            let center = ((i as f64) + 0.5) * scale;
+            let start = (center - radius).floor().max(0.0) as usize;
+            let end = ((center + radius).ceil() as usize).min(size);
+
+            let weights: Vec<f64> = (start..end).map(|j| self.weight(((j as f64) + 0.5 - center) / scale)).collect();
+            let total = weights.iter().fold(0.0, |sum, weight| sum + weight);
+            let normalization = if total != 0.0 { scale / total } else { 0.0 };
+
+            (start, weights.into_iter().map(|weight| weight * normalization).collect())
+        }).collect()
+    }
+}
+
+/// Scales colors accumulated at `factor` times their final size down,
+/// weighting the accumulated pixels around every final one by the filter.
+/// Leftover rows and columns are dropped.
+pub fn downsample(colors: &[Color], width: usize, height: usize, factor: usize, filter: Filter) -> Vec<Color> {
+    let (small_width, small_height) = (width / factor, height / factor);
+    let (column_weights, row_weights) = (filter.weights(width, factor), filter.weights(height, factor));
+
+    // The filters are separable, so rows get filtered first and columns after
+    let mut rows = Vec::with_capacity(small_width * height);
+    for y in 0..height {
+        for &(start, ref weights) in column_weights.iter() {
+            rows.push(weights.iter().enumerate().fold(Color::new(), |sum, (i, weight)| {
+                &sum + &(&colors[y * width + start + i] * *weight)
+            }));
+        }
+    }
+
+    let mut small = Vec::with_capacity(small_width * small_height);
+    for &(start, ref weights) in row_weights.iter() {
+        for x in 0..small_width {
+            let color = weights.iter().enumerate().fold(Color::new(), |sum, (i, weight)| {
+                &sum + &(&rows[(start + i) * small_width + x] * *weight)
+            });
+
+            // Mitchell's negative lobes can overshoot around sharp edges
+            small.push(Color { r: color.r.max(0.0), g: color.g.max(0.0), b: color.b.max(0.0), a: color.a.max(0.0) });
+        }
+    }
+
+    small
+}
+
+/// Accumulates particles into an image as described by a flame's render
+/// settings, without going through the reactor's output stream.
+pub struct Renderer {
+    settings: RenderSettings,
+    palette: ImagePalette,
+    colors: Vec<Color>,
+    count: u64
+}
+
+impl Renderer {
+    pub fn new(settings: RenderSettings, palette: ImagePalette) -> Renderer {
+        let size = settings.width * settings.height;
+
+        Renderer {
+            settings: settings,
+            palette: palette,
+            colors: vec![Color::new(); size],
+            count: 0
+        }
+    }
+
+    /// Uses the palette from the settings, falling back like
+    /// `ImagePalette::from_settings`.
+    pub fn from_settings(settings: RenderSettings) -> Result<Renderer, image::ImageError> {
+        let palette = try!(ImagePalette::from_settings(&settings));
+
+        Ok(Renderer::new(settings, palette))
+    }
+
+    /// Renders the system with its own render settings.
+    pub fn for_system(system: &System) -> Result<Renderer, image::ImageError> {
+        Renderer::from_settings(system.render_settings.clone())
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn width(&self) -> usize {
+        self.settings.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.settings.height
+    }
+
+    /// Accumulated colors, row by row.
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Number of particles that landed inside the image.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn exposure_and_gamma(&self) -> (f64, f64) {
+        exposure_and_gamma(self.count, &self.settings)
+    }
+
+    pub fn add(&mut self, particle: &Particle) {
+        let (width, height) = (self.settings.width, self.settings.height);
+        let projected = self.settings.camera.project(&particle.point, width, height);
+        let (x, y) = (projected.x, projected.y);
+
+        if x >= 0.0 && y >= 0.0 && x < (width as f64) && y < (height as f64) {
+            let pixel = (y.trunc() as usize) * width + (x.trunc() as usize);
+
+            self.colors[pixel] = &self.colors[pixel] + &self.palette.color_at(particle.color);
+            self.count += 1;
+        }
+    }
+
+    /// Iterates the system in-process, adding every step of `particle_count`
+    /// particles that live for `iteration_count` iterations each.
+    pub fn run<R: Rng>(&mut self, system: &System, particle_count: usize, iteration_count: u32, rng: &mut R) {
+        for _ in 0..particle_count {
+            let mut particle = system.make_particle(rng);
+
+            for _ in 0..iteration_count {
+                if let Some(output) = system.step(&mut particle, rng) {
+                    self.add(&output);
+                }
+            }
+        }
+    }
+
+    /// Adds particles as written by `Particle::bytes` until the stream ends.
+    pub fn read_particles<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let mut bytes: [u8; 8*3] = [0; 8 * 3];
+
+        loop {
+            match reader.read_exact(&mut bytes) {
+                Ok(()) => self.add(&Particle::from_bytes(&bytes)),
+                Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    /// Reads histogram frames written by `reactor --output histogram`,
+    /// keeping the last one as it contains everything accumulated so far. The
+    /// image takes the size of the frames.
+    ///
+    /// The reactor already looked the colors up in the flame's palette, so
+    /// this renderer's palette isn't used.
+    pub fn read_histogram<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let mut header: [u8; 4*2] = [0; 4 * 2];
+        let mut bin_bytes: [u8; 8*5] = [0; 8 * 5];
+
+        while reader.read_exact(&mut header).is_ok() {
+            self.settings.width = BigEndian::read_u32(&header[4*0..4*1]) as usize;
+            self.settings.height = BigEndian::read_u32(&header[4*1..4*2]) as usize;
+
+            self.colors = Vec::with_capacity(self.settings.width * self.settings.height);
+            self.count = 0;
+
+            for _ in 0..(self.settings.width * self.settings.height) {
+                try!(reader.read_exact(&mut bin_bytes));
+
+                let density = BigEndian::read_f64(&bin_bytes[8*0..8*1]);
+
+                self.count += density as u64;
+                self.colors.push(Color {
+                    r: BigEndian::read_f64(&bin_bytes[8*1..8*2]),
+                    g: BigEndian::read_f64(&bin_bytes[8*2..8*3]),
+                    b: BigEndian::read_f64(&bin_bytes[8*3..8*4]),
+                    a: BigEndian::read_f64(&bin_bytes[8*4..8*5])
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tone maps what has been accumulated so far into an opaque RGBA image.
+    pub fn image(&self) -> image::RgbaImage {
+        let rgb = make_image(&self.colors, self.count, &self.settings);
+        let rgba = rgb.chunks(3).flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 255].into_iter()).collect();
+
+        image::ImageBuffer::from_raw(self.settings.width as u32, self.settings.height as u32, rgba).unwrap()
+    }
+
+    /// Starts over with an empty image.
+    pub fn clear(&mut self) {
+        self.colors = vec![Color::new(); self.settings.width * self.settings.height];
+        self.count = 0;
+    }
+}
@@ -0,0 +1,630 @@
+use types::{Particle, Message};
+use types::system::*;
+use interop;
+use interop::Format;
+use histogram::Histogram;
+use render::ImagePalette;
+use rand;
+use rand::{SeedableRng, XorShiftRng};
+use crossbeam;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::mem;
+use std::time::{Duration, Instant};
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+use std::net::TcpListener;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::env;
+
+enum Status<'a> {
+    Generated(Vec<Particle>),
+    Accumulated(Histogram<'a>),
+    Finished,
+}
+
+/// Counts of what happened during a run.
+#[derive(Clone, Debug)]
+pub struct Statistics {
+    /// Steps done over all particles.
+    pub iterations: u64,
+    /// Particles reset because their time to live ran out.
+    pub resets: u64,
+    /// Steps without output because the particle diverged, which resets it,
+    /// or only the final transform's output did, which doesn't. See
+    /// `System::step`.
+    pub bad_values: u64,
+    /// How many times every transform got picked, in the flame's order.
+    pub transform_hits: Vec<u64>
+}
+
+impl Statistics {
+    pub fn new(transform_count: usize) -> Statistics {
+        Statistics {
+            iterations: 0,
+            resets: 0,
+            bad_values: 0,
+            transform_hits: vec![0; transform_count]
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Statistics::new(self.transform_hits.len());
+    }
+}
+
+/// Statistics shared between the workers of a run, which add to them as they
+/// go, and its progress reporter.
+struct Progress {
+    iterations: AtomicUsize,
+    resets: AtomicUsize,
+    bad_values: AtomicUsize,
+    transform_hits: Vec<AtomicUsize>
+}
+
+impl Progress {
+    fn new(transform_count: usize) -> Progress {
+        Progress {
+            iterations: AtomicUsize::new(0),
+            resets: AtomicUsize::new(0),
+            bad_values: AtomicUsize::new(0),
+            transform_hits: (0..transform_count).map(|_| AtomicUsize::new(0)).collect()
+        }
+    }
+
+    fn add(&self, statistics: &Statistics) {
+        self.iterations.fetch_add(statistics.iterations as usize, Ordering::Relaxed);
+        self.resets.fetch_add(statistics.resets as usize, Ordering::Relaxed);
+        self.bad_values.fetch_add(statistics.bad_values as usize, Ordering::Relaxed);
+
+        for (hits, new_hits) in self.transform_hits.iter().zip(statistics.transform_hits.iter()) {
+            hits.fetch_add(*new_hits as usize, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> Statistics {
+        Statistics {
+            iterations: self.iterations.load(Ordering::Relaxed) as u64,
+            resets: self.resets.load(Ordering::Relaxed) as u64,
+            bad_values: self.bad_values.load(Ordering::Relaxed) as u64,
+            transform_hits: self.transform_hits.iter().map(|hits| hits.load(Ordering::Relaxed) as u64).collect()
+        }
+    }
+}
+
+/// How far a run got, passed to its `Reporter`.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub statistics: Statistics,
+    /// Iterations the run was asked to do over all particles.
+    pub total_iterations: u64,
+    pub elapsed: Duration,
+    /// Set for the last report, sent when the run ends or gets stopped.
+    pub finished: bool
+}
+
+/// Receives the progress of every run, see `Config::progress_interval`.
+pub type Reporter = Arc<Fn(&Report) + Send + Sync>;
+
+/// What the reactor sends back to its client.
+#[derive(Clone, Debug)]
+pub enum Output {
+    /// Every generated particle, as written by `Particle::bytes`.
+    Particles,
+    /// Histogram frames sized and framed according to the flame's render
+    /// settings, see `Histogram::write_to`.
+    Histogram
+}
+
+impl Output {
+    pub fn parse(output: &str) -> Result<Output, &'static str> {
+        match output {
+            "particles" => Ok(Output::Particles),
+            "histogram" => Ok(Output::Histogram),
+            _ => Err("Output must be either particles or histogram")
+        }
+    }
+}
+
+// Fixed rather than the number of CPUs, since every thread gets its own chunk
+// of particles and RNG and seeded runs have to match across machines
+const DEFAULT_THREAD_COUNT: usize = 4;
+
+/// Generation settings shared by every run of a reactor process.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub particle_count: usize,
+    pub iteration_count: u32,
+    pub particle_buffer_size: usize,
+    pub channel_size: usize,
+    pub thread_count: usize,
+    pub output: Output,
+    /// Number of iterations after which an updated histogram gets written,
+    /// only once at the end of the run if not set.
+    pub histogram_interval: Option<u32>,
+    /// Overrides the seed stored in the flame when set.
+    pub seed: Option<u64>,
+    /// Encoding of the messages read from clients.
+    pub format: Format,
+    /// Seconds between progress reports, only a summary gets reported at
+    /// the end of the run if not set.
+    pub progress_interval: Option<u64>
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        Config {
+            particle_count: env::var("PARTICLE_COUNT").unwrap_or("10000".to_string()).parse::<usize>().unwrap(),
+            iteration_count: env::var("ITERATION_COUNT").unwrap_or("1000".to_string()).parse::<u32>().unwrap(),
+            particle_buffer_size: env::var("PARTICLE_BUFFER_SIZE").unwrap_or("1000".to_string()).parse::<usize>().unwrap(),
+            channel_size: env::var("CHANNEL_SIZE").unwrap_or("10".to_string()).parse::<usize>().unwrap(),
+            thread_count: env::var("THREAD_COUNT").unwrap_or(DEFAULT_THREAD_COUNT.to_string()).parse::<usize>().unwrap(),
+            output: Output::parse(&env::var("OUTPUT").unwrap_or("particles".to_string())).unwrap(),
+            histogram_interval: env::var("HISTOGRAM_INTERVAL").ok().map(|interval| interval.parse::<u32>().unwrap()),
+            seed: env::var("SEED").ok().map(|seed| seed.parse::<u64>().unwrap()),
+            format: Format::parse(&env::var("FORMAT").unwrap_or("capnp".to_string())).unwrap(),
+            progress_interval: env::var("PROGRESS_INTERVAL").ok().map(|interval| interval.parse::<u64>().unwrap())
+        }
+    }
+}
+
+// SplitMix64, used to spread a single seed over the state of every worker's RNG
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn worker_rng(seed: u64, worker: usize) -> XorShiftRng {
+    let mut state = seed ^ (worker as u64).wrapping_mul(0xD1B54A32D192ED03);
+    let (a, b) = (split_mix(&mut state), split_mix(&mut state));
+
+    XorShiftRng::from_seed([(a >> 32) as u32, a as u32, (b >> 32) as u32, (b as u32) | 1])
+}
+
+/// Runs the system and writes the generated particles, or histograms of them,
+/// to `writer` until all iterations are done or `running` gets cleared.
+///
+/// Every worker owns a fixed chunk of particles and an RNG derived from the
+/// seed, and their buffers are written in round-robin order, so the same seed
+/// and config always produce the same stream.
+///
+/// Progress is passed to `reporter` every `progress_interval` seconds and once
+/// more when the run ends, the final statistics are also returned.
+pub fn generate<W: Write>(system: &System, config: &Config, writer: &mut W, running: &AtomicBool, reporter: &(Fn(&Report) + Send + Sync)) -> Statistics {
+    let seed = config.seed.or(system.seed).unwrap_or_else(|| rand::random::<u64>());
+    let particle_buffer_size = config.particle_buffer_size;
+    let iteration_count = config.iteration_count;
+    let histogram_interval = config.histogram_interval;
+    let camera = system.render_settings.camera;
+
+    // Histograms sum colors like the client does, so they need the palette up front
+    let palette = match config.output {
+        Output::Histogram => match ImagePalette::from_settings(&system.render_settings) {
+            Ok(palette) => Some(palette),
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "Error: {}", e);
+                return Statistics::new(system.transforms().len());
+            }
+        },
+        Output::Particles => None
+    };
+    let histogram_settings = palette.as_ref().map(|palette| (system.render_settings.width, system.render_settings.height, palette));
+
+    let chunk_size = ((config.particle_count as f32) / (config.thread_count as f32)).ceil() as usize;
+    let mut particles: Vec<Particle> = vec![Particle::new(); config.particle_count];
+
+    let transform_count = system.transforms().len();
+    let total_iterations = (config.particle_count as u64) * (iteration_count as u64);
+    let started = Instant::now();
+    let progress = Progress::new(transform_count);
+    let progress = &progress;
+
+    crossbeam::scope(|scope| {
+        let (stop_reporting, reporting_stopped) = mpsc::channel::<()>();
+
+        if let Some(interval) = config.progress_interval {
+            scope.spawn(move|| {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = reporting_stopped.recv_timeout(Duration::from_secs(interval)) {
+                    reporter(&Report {
+                        statistics: progress.snapshot(),
+                        total_iterations: total_iterations,
+                        elapsed: started.elapsed(),
+                        finished: false
+                    });
+                }
+            });
+        }
+
+        let mut receivers = Vec::with_capacity(config.thread_count);
+
+        for (worker, particle_chunk) in particles.chunks_mut(chunk_size).enumerate() {
+            let (tx, rx) = mpsc::sync_channel(config.channel_size);
+            receivers.push(rx);
+
+            scope.spawn(move|| {
+                let mut rng = worker_rng(seed, worker);
+
+                for particle in particle_chunk.iter_mut() {
+                    *particle = system.make_particle(&mut rng);
+                }
+
+                let mut buffer = Vec::with_capacity(particle_buffer_size);
+                let mut histogram = histogram_settings.map(|(width, height, palette)| Histogram::new(width, height, camera, palette));
+                let mut worker_statistics = Statistics::new(transform_count);
+
+                for iteration in 0..iteration_count {
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    for particle in particle_chunk.iter_mut() {
+                        worker_statistics.iterations += 1;
+                        if particle.ttl <= 0 {
+                            worker_statistics.resets += 1;
+                        }
+
+                        let (index, projected_particle) = system.step_with_transform(particle, &mut rng);
+                        worker_statistics.transform_hits[index] += 1;
+
+                        let projected_particle = match projected_particle {
+                            Some(projected_particle) => projected_particle,
+                            None => {
+                                worker_statistics.bad_values += 1;
+                                continue;
+                            }
+                        };
+
+                        match histogram {
+                            Some(ref mut histogram) => histogram.add(&projected_particle),
+                            None => {
+                                buffer.push(projected_particle);
+
+                                if buffer.len() == particle_buffer_size {
+                                    tx.send(Status::Generated(mem::replace(&mut buffer, Vec::with_capacity(particle_buffer_size)))).unwrap();
+                                }
+                            }
+                        }
+                    }
+
+                    progress.add(&worker_statistics);
+                    worker_statistics.clear();
+
+                    if let (Some(histogram), Some((width, height, palette)), Some(interval)) = (histogram.as_mut(), histogram_settings, histogram_interval) {
+                        if (iteration + 1) % interval == 0 {
+                            tx.send(Status::Accumulated(mem::replace(histogram, Histogram::new(width, height, camera, palette)))).unwrap();
+                        }
+                    }
+                }
+
+                if buffer.len() > 0 {
+                    tx.send(Status::Generated(buffer)).unwrap();
+                }
+
+                if let Some(histogram) = histogram {
+                    if !histogram.is_empty() {
+                        tx.send(Status::Accumulated(histogram)).unwrap();
+                    }
+                }
+
+                tx.send(Status::Finished).unwrap();
+            });
+        }
+
+        let mut total = histogram_settings.map(|(width, height, palette)| Histogram::new(width, height, camera, palette));
+        let mut total_changed = false;
+
+        let mut index: usize = 0;
+        while !receivers.is_empty() {
+            index %= receivers.len();
+
+            match receivers[index].recv().unwrap() {
+                Status::Generated(buffer) => {
+                    index += 1;
+
+                    if !running.load(Ordering::Relaxed) {
+                        continue;
+                    }
+
+                    for particle in buffer.iter() {
+                        if writer.write_all(&particle.bytes()).is_err() {
+                            // The client went away, there's no point in generating more
+                            running.store(false, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                },
+                Status::Accumulated(histogram) => {
+                    index += 1;
+
+                    if let Some(ref mut total) = total {
+                        total.merge(&histogram);
+                        total_changed = true;
+
+                        // Write a snapshot once every worker had a chance to contribute
+                        if index == receivers.len() && running.load(Ordering::Relaxed) {
+                            if total.write_to(writer).is_err() {
+                                running.store(false, Ordering::Relaxed);
+                            }
+                            total_changed = false;
+                        }
+                    }
+                },
+                Status::Finished => {
+                    receivers.remove(index);
+                }
+            }
+        }
+
+        if let Some(ref total) = total {
+            if total_changed && running.load(Ordering::Relaxed) {
+                let _ = total.write_to(writer);
+            }
+        }
+
+        drop(stop_reporting);
+    });
+
+    let _ = writer.flush();
+
+    let statistics = progress.snapshot();
+    reporter(&Report {
+        statistics: statistics.clone(),
+        total_iterations: total_iterations,
+        elapsed: started.elapsed(),
+        finished: true
+    });
+
+    statistics
+}
+
+/// A generation running in the background, owning the output writer until it
+/// gets stopped or finishes.
+struct Job<W> {
+    running: Arc<AtomicBool>,
+    handle: thread::JoinHandle<W>
+}
+
+impl<W: Write + Send + 'static> Job<W> {
+    fn start(system: System, config: Config, mut writer: W, reporter: Reporter) -> Job<W> {
+        let running = Arc::new(AtomicBool::new(true));
+        let job_running = running.clone();
+
+        let handle = thread::spawn(move|| {
+            generate(&system, &config, &mut writer, &job_running, &*reporter);
+            writer
+        });
+
+        Job { running: running, handle: handle }
+    }
+
+    fn stop(self) -> W {
+        self.running.store(false, Ordering::Relaxed);
+        self.wait()
+    }
+
+    fn wait(self) -> W {
+        self.handle.join().unwrap()
+    }
+}
+
+/// Reads messages from `reader` for as long as it stays open, starting a new
+/// generation for every `Start` and cancelling the current one on `Stop` or
+/// when it gets superseded.
+pub fn serve<R, W>(reader: R, writer: W, config: Config, reporter: Reporter)
+    where R: Read + Send + 'static, W: Write + Send + 'static
+{
+    let (tx, rx) = mpsc::channel();
+    let format = config.format;
+
+    thread::spawn(move|| {
+        let mut reader = BufReader::new(reader);
+
+        loop {
+            let result = match format {
+                Format::Capnp => {
+                    let at_eof = match reader.fill_buf() {
+                        Ok(buf) => buf.is_empty(),
+                        Err(_) => true
+                    };
+
+                    if at_eof {
+                        break;
+                    }
+
+                    interop::read_message(&mut reader)
+                },
+                Format::Json => match interop::read_json_message(&mut reader) {
+                    Ok(Some(message)) => Ok(message),
+                    Ok(None) => break,
+                    Err(e) => Err(e)
+                }
+            };
+
+            let failed = result.is_err();
+
+            if tx.send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let mut writer = Some(writer);
+    let mut job: Option<Job<W>> = None;
+
+    for result in rx.iter() {
+        match result {
+            Ok(Message::Start(system)) => {
+                if let Some(current) = job.take() {
+                    writer = Some(current.stop());
+                }
+
+                job = Some(Job::start(system, config.clone(), writer.take().unwrap(), reporter.clone()));
+            },
+            Ok(Message::Stop) => {
+                if let Some(current) = job.take() {
+                    writer = Some(current.stop());
+                }
+            },
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "Error: {}", e);
+            }
+        }
+    }
+
+    if let Some(current) = job {
+        current.wait();
+    }
+}
+
+/// Where the reactor accepts client connections.
+#[derive(Debug)]
+pub enum Address {
+    Unix(PathBuf),
+    Tcp(String)
+}
+
+impl Address {
+    /// Parses addresses in the `unix:/path/to/socket` or `tcp:host:port` form.
+    pub fn parse(address: &str) -> Result<Address, &'static str> {
+        if address.starts_with("unix:") {
+            Ok(Address::Unix(PathBuf::from(&address[5..])))
+        } else if address.starts_with("tcp:") {
+            Ok(Address::Tcp(address[4..].to_string()))
+        } else {
+            Err("Address must start with unix: or tcp:")
+        }
+    }
+}
+
+/// Removes the socket file of a Unix listener once it's done with it.
+struct SocketFile<'a>(&'a Path);
+
+impl<'a> Drop for SocketFile<'a> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.0);
+    }
+}
+
+/// Removes the socket file left behind by a reactor that didn't exit
+/// cleanly, leaving it alone if something still accepts connections on it.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    if fs::metadata(path).is_err() {
+        return Ok(());
+    }
+
+    match UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is in use by another process", path.display()))),
+        Err(_) => fs::remove_file(path)
+    }
+}
+
+/// Accepts connections on `address` forever, serving every client on its own
+/// thread and streaming particles back over the same connection.
+///
+/// A Unix socket file is removed again when this returns, and one left over
+/// from an earlier process that got killed is replaced.
+pub fn listen(address: &Address, config: &Config, reporter: &Reporter) -> io::Result<()> {
+    match *address {
+        Address::Unix(ref path) => {
+            try!(remove_stale_socket(path));
+
+            let listener = try!(UnixListener::bind(path));
+            let _socket_file = SocketFile(path);
+
+            for stream in listener.incoming() {
+                let stream = try!(stream);
+                let (reader, config, reporter) = (try!(stream.try_clone()), config.clone(), reporter.clone());
+
+                thread::spawn(move|| serve(reader, BufWriter::new(stream), config, reporter));
+            }
+        },
+        Address::Tcp(ref address) => {
+            let listener = try!(TcpListener::bind(&address[..]));
+
+            for stream in listener.incoming() {
+                let stream = try!(stream);
+                let (reader, config, reporter) = (try!(stream.try_clone()), config.clone(), reporter.clone());
+
+                thread::spawn(move|| serve(reader, BufWriter::new(stream), config, reporter));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, Config, Output, Report};
+    use interop::Format;
+    use types::{System, AffineTransformation};
+    use types::system::SystemBuilder;
+    use types::transform::TransformBuilder;
+    use variations::{DeJong, Linear};
+    use std::sync::atomic::AtomicBool;
+
+    fn system() -> System {
+        let dejong = TransformBuilder::new()
+            .add_boxed_variation(Box::new(DeJong(1.66, -0.68, 1.47, 1.79)))
+            .finalize();
+        let shrink = TransformBuilder::new()
+            .pre(AffineTransformation::from_coefficients(0.5, 0.0, 0.25, 0.0, 0.5, -0.25))
+            .add_boxed_variation(Box::new(Linear))
+            .finalize();
+
+        SystemBuilder::new()
+            .add_weighted_transform(dejong, 1.0)
+            .add_weighted_transform(shrink, 0.5)
+            .finalize()
+            .unwrap()
+    }
+
+    // Three workers and a particle count that doesn't split evenly between them
+    fn config(seed: u64) -> Config {
+        Config {
+            particle_count: 1001,
+            iteration_count: 20,
+            particle_buffer_size: 64,
+            channel_size: 4,
+            thread_count: 3,
+            output: Output::Particles,
+            histogram_interval: None,
+            seed: Some(seed),
+            format: Format::Capnp,
+            progress_interval: None
+        }
+    }
+
+    fn run(system: &System, config: &Config) -> Vec<u8> {
+        let mut output = Vec::new();
+        generate(system, config, &mut output, &AtomicBool::new(true), &|_: &Report| {});
+
+        output
+    }
+
+    #[test]
+    fn same_seed_gives_identical_streams() {
+        let system = system();
+
+        let first = run(&system, &config(42));
+        let second = run(&system, &config(42));
+
+        assert_eq!(first.len(), 1001 * 20 * 24);
+        assert!(first == second);
+    }
+
+    #[test]
+    fn different_seeds_give_different_streams() {
+        let system = system();
+
+        assert!(run(&system, &config(42)) != run(&system, &config(43)));
+    }
+}
@@ -7,6 +7,16 @@ impl AffineTransformation {
     pub fn identity() -> AffineTransformation {
         AffineTransformationBuilder::new().finalize()
     }
+
+    /// Creates a transformation mapping (x, y) to (ax + by + c, dx + ey + f).
+    pub fn from_coefficients(a: Number, b: Number, c: Number, d: Number, e: Number, f: Number) -> AffineTransformation {
+        AffineTransformation(a, b, c, d, e, f)
+    }
+
+    /// The coefficients in the order `from_coefficients` takes them.
+    pub fn coefficients(&self) -> [Number; 6] {
+        [self.0, self.1, self.2, self.3, self.4, self.5]
+    }
 }
 
 impl Applicable for AffineTransformation {
@@ -2,6 +2,13 @@ use types::{Number, Particle, Point};
 
 pub trait ColoringMethod: Sync + Send + ::std::fmt::Debug {
     fn color(&self, old: &Particle, new: &Point) -> Number;
+
+    /// The name of the method in messages, in camel case.
+    fn name(&self) -> &str;
+
+    fn params(&self) -> Vec<Number> {
+        Vec::new()
+    }
 }
 
 #[derive(Debug)]
@@ -11,6 +18,10 @@ impl ColoringMethod for Noop {
     fn color(&self, old: &Particle, _new: &Point) -> Number {
         old.color
     }
+
+    fn name(&self) -> &str {
+        "noop"
+    }
 }
 
 #[derive(Debug)]
@@ -23,21 +34,41 @@ impl ColoringMethod for Distance {
 
         (dx * dx + dy * dy).sqrt().max(0.0).min(1.0)
     }
+
+    fn name(&self) -> &str {
+        "distance"
+    }
 }
 
+/// Moves the color towards a fixed one by `speed` every step, like flam3's
+/// `color_speed`.
 #[derive(Debug)]
 pub struct SingleColor {
-    color: Number
+    color: Number,
+    speed: Number
 }
 
 impl SingleColor {
+    /// Moves halfway towards the color.
     pub fn new(color: Number) -> SingleColor {
-        SingleColor { color: color }
+        SingleColor::with_speed(color, 0.5)
+    }
+
+    pub fn with_speed(color: Number, speed: Number) -> SingleColor {
+        SingleColor { color: color, speed: speed }
     }
 }
 
 impl ColoringMethod for SingleColor {
     fn color(&self, old: &Particle, _new: &Point) -> Number {
-        (self.color + old.color) / 2.0
+        self.speed * self.color + (1.0 - self.speed) * old.color
+    }
+
+    fn name(&self) -> &str {
+        "singleColor"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.color, self.speed]
     }
 }
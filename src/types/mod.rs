@@ -3,6 +3,9 @@ pub use self::particle::Particle;
 pub use self::system::System;
 pub use self::affine_transformation::AffineTransformation;
 pub use self::transform::Transform;
+pub use self::render_settings::RenderSettings;
+
+use rand::Rng;
 
 pub type Number = f64;
 
@@ -11,7 +14,17 @@ pub trait Applicable {
 }
 
 pub trait Variation: Send + Sync + ::std::fmt::Debug {
-    fn apply(&self, point: &Point, transformation: &AffineTransformation) -> Point;
+    /// Stochastic variations draw from `rng`, which is the generating worker's
+    /// own so seeded runs stay reproducible.
+    fn apply(&self, point: &Point, transformation: &AffineTransformation, rng: &mut Rng) -> Point;
+
+    /// The name the variation is known by in messages.
+    fn name(&self) -> &str;
+
+    /// Parameters that recreate the variation when passed to `make_variation`.
+    fn params(&self) -> Vec<Number> {
+        Vec::new()
+    }
 }
 
 pub enum Message {
@@ -25,3 +38,4 @@ pub mod system;
 pub mod affine_transformation;
 pub mod transform;
 pub mod coloring_method;
+pub mod render_settings;
@@ -6,7 +6,10 @@ use byteorder::{ByteOrder, BigEndian};
 pub struct Particle {
     pub point: Point,
     pub color: Number,
-    pub ttl: i32
+    pub ttl: i32,
+    /// Index of the transform applied last, which picks the xaos row used
+    /// for the next one. `None` for fresh particles.
+    pub last_transform: Option<usize>
 }
 
 impl Particle {
@@ -14,7 +17,8 @@ impl Particle {
         Particle {
             point: Point::new(),
             color: 0.5,
-            ttl: 1
+            ttl: 1,
+            last_transform: None
         }
     }
 
@@ -28,6 +32,16 @@ impl Particle {
         buffer
     }
 
+    /// Reads a particle written by `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Particle {
+        Particle {
+            point: Point::from_xy(BigEndian::read_f64(&bytes[8*0..8*1]), BigEndian::read_f64(&bytes[8*1..8*2])),
+            color: BigEndian::read_f64(&bytes[8*2..8*3]),
+            ttl: 1,
+            last_transform: None
+        }
+    }
+
     pub fn age(&mut self) -> &mut Particle {
         self.ttl -= 1;
         self
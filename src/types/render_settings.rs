@@ -0,0 +1,143 @@
+use std::f64;
+use types::{Number, Point};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub center: Point,
+    pub zoom: Number,
+    pub rotation: Number
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            center: Point::new(),
+            zoom: 1.0,
+            rotation: 0.0
+        }
+    }
+
+    /// Maps a point onto an image of the given size, returning its position in
+    /// pixels. At zoom 1 the shorter side of the image spans from -2 to 2.
+    pub fn project(&self, point: &Point, width: usize, height: usize) -> Point {
+        let scale = (width.min(height) as Number) / 4.0 * self.zoom;
+        let (sin, cos) = (self.rotation.sin(), self.rotation.cos());
+
+        let dx = point.x - self.center.x;
+        let dy = point.y - self.center.y;
+
+        Point::from_xy(
+            (width as Number) / 2.0 + (dx * cos + dy * sin) * scale,
+            (height as Number) / 2.0 + (dy * cos - dx * sin) * scale
+        )
+    }
+
+    /// Frames the points in an image of the given size, without rotation.
+    /// The outermost `percentile` of the points on every side is ignored, so
+    /// a few stray ones don't shrink the attractor, and `margin` is the
+    /// fraction of the image left free around the rest.
+    pub fn fit(points: &[Point], width: usize, height: usize, percentile: Number, margin: Number) -> Camera {
+        let mut xs: Vec<Number> = points.iter().map(|point| point.x).filter(|x| x.is_finite()).collect();
+        let mut ys: Vec<Number> = points.iter().map(|point| point.y).filter(|y| y.is_finite()).collect();
+
+        if xs.is_empty() || ys.is_empty() {
+            return Camera::new();
+        }
+
+        let (min_x, max_x) = percentile_bounds(&mut xs, percentile);
+        let (min_y, max_y) = percentile_bounds(&mut ys, percentile);
+
+        // Pixels available per unit along each side, unbounded for a side with no extent
+        let free = 1.0 - 2.0 * margin;
+        let scale_x = if max_x > min_x { (width as Number) * free / (max_x - min_x) } else { f64::INFINITY };
+        let scale_y = if max_y > min_y { (height as Number) * free / (max_y - min_y) } else { f64::INFINITY };
+        let scale = scale_x.min(scale_y);
+
+        Camera {
+            center: Point::from_xy((min_x + max_x) / 2.0, (min_y + max_y) / 2.0),
+            zoom: if scale.is_finite() { scale * 4.0 / (width.min(height) as Number) } else { 1.0 },
+            rotation: 0.0
+        }
+    }
+}
+
+fn percentile_bounds(values: &mut [Number], percentile: Number) -> (Number, Number) {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let last = values.len() - 1;
+    let skipped = ((last as Number) * percentile.max(0.0).min(0.5)).round() as usize;
+
+    (values[skipped], values[last - skipped])
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PaletteStop {
+    pub position: Number,
+    /// Red, green, blue and alpha, from 0 to 1.
+    pub color: (Number, Number, Number, Number)
+}
+
+#[derive(Clone, Debug)]
+pub enum PaletteSource {
+    File(String),
+    Stops(Vec<PaletteStop>),
+    /// An encoded image, colors are taken from its first row.
+    Image(Vec<u8>)
+}
+
+/// flam3's density estimation: every pixel gets spread over a kernel with a
+/// radius of `max_radius / density^curve` pixels, but at least `min_radius`,
+/// so sparse areas get smoothed while dense ones stay sharp.
+#[derive(Copy, Clone, Debug)]
+pub struct DensityEstimation {
+    pub max_radius: Number,
+    pub min_radius: Number,
+    pub curve: Number
+}
+
+impl DensityEstimation {
+    /// flam3's defaults.
+    pub fn new() -> DensityEstimation {
+        DensityEstimation {
+            max_radius: 9.0,
+            min_radius: 0.0,
+            curve: 0.4
+        }
+    }
+
+    /// Kernel radius for a pixel of that density.
+    pub fn radius(&self, density: Number) -> Number {
+        (self.max_radius / density.powf(self.curve)).max(self.min_radius)
+    }
+}
+
+/// Describes how the generated particles should be turned into an image.
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub camera: Camera,
+    /// Picked based on the number of points when `None`.
+    pub gamma: Option<Number>,
+    /// In stops, picked based on the number of points when `None`.
+    pub exposure: Option<Number>,
+    pub background: (Number, Number, Number),
+    pub palette: Option<PaletteSource>,
+    /// No density estimation when `None`.
+    pub density_estimation: Option<DensityEstimation>
+}
+
+impl RenderSettings {
+    pub fn new() -> RenderSettings {
+        RenderSettings {
+            width: 1024,
+            height: 1024,
+            camera: Camera::new(),
+            gamma: None,
+            exposure: None,
+            background: (0.0, 0.0, 0.0),
+            palette: None,
+            density_estimation: None
+        }
+    }
+}
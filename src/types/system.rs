@@ -1,24 +1,99 @@
-use types::{Particle, Point, Number, Transform, Applicable, AffineTransformation};
+use types::{Particle, Point, Number, Transform, Applicable, AffineTransformation, RenderSettings};
 use types::transform::TransformBuilder;
-use rand::{random, Rng};
+use rand::Rng;
 use rand::distributions::{IndependentSample, Range};
 
 #[derive(Debug)]
-struct TransformWithLimit {
+struct WeightedTransform {
     transform: Transform,
-    limit: f64
+    weight: f64,
+    xaos: Vec<f64>
+}
+
+/// Table for Walker's alias method, which picks a transform in constant time:
+/// every column holds a transform with some probability and another one, its
+/// alias, taking the rest.
+#[derive(Debug)]
+struct AliasTable {
+    probabilities: Vec<f64>,
+    aliases: Vec<usize>
+}
+
+impl AliasTable {
+    /// `None` when there's nothing with a positive weight to pick.
+    fn new(weights: &[f64]) -> Option<AliasTable> {
+        let count = weights.len();
+        let total = weights.iter().fold(0.0, |sum, weight| sum + weight);
+
+        if count == 0 || !(total > 0.0) {
+            return None;
+        }
+
+        let mut probabilities: Vec<f64> = weights.iter().map(|weight| weight * (count as f64) / total).collect();
+        let mut aliases: Vec<usize> = (0..count).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..count).partition(|&i| probabilities[i] < 1.0);
+
+        while !small.is_empty() && !large.is_empty() {
+            let (less, more) = (small.pop().unwrap(), large.pop().unwrap());
+
+            aliases[less] = more;
+            probabilities[more] -= 1.0 - probabilities[less];
+
+            if probabilities[more] < 1.0 { small.push(more) } else { large.push(more) }
+        }
+
+        // Whatever is left is only off from 1 by rounding errors
+        for i in small.into_iter().chain(large.into_iter()) {
+            probabilities[i] = 1.0;
+        }
+
+        Some(AliasTable {
+            probabilities: probabilities,
+            aliases: aliases
+        })
+    }
+
+    fn pick<R: Rng>(&self, rng: &mut R) -> usize {
+        let count = self.probabilities.len();
+        let value = rng.next_f64() * (count as f64);
+
+        // The integer part picks the column, the fractional part the side
+        let column = (value as usize).min(count - 1);
+        if value - (column as f64) < self.probabilities[column] { column } else { self.aliases[column] }
+    }
 }
 
 #[derive(Debug)]
 pub struct System {
-    transforms: Vec<TransformWithLimit>,
-    max_range: f64,
+    transforms: Vec<WeightedTransform>,
+    selection: AliasTable,
+    /// Tables used after every transform, `None` where its xaos row doesn't
+    /// change the weights.
+    xaos_selections: Vec<Option<AliasTable>>,
     pub final_transform: Transform,
     pub ttl: i32,
-    pub reset_transformation: AffineTransformation
+    /// Iterations done after every reset before the particle gets output.
+    pub fuse: u32,
+    /// Particles with a coordinate beyond this, in either direction, are
+    /// considered diverged.
+    pub bound: Number,
+    pub reset_transformation: AffineTransformation,
+    pub seed: Option<u64>,
+    pub render_settings: RenderSettings
 }
 
 impl System {
+    /// The transforms together with their weights, in the order they were added.
+    pub fn transforms(&self) -> Vec<(&Transform, f64)> {
+        self.transforms.iter().map(|transform| (&transform.transform, transform.weight)).collect()
+    }
+
+    /// The xaos rows of the transforms, as they were given to the builder.
+    pub fn xaos(&self) -> Vec<&[f64]> {
+        self.transforms.iter().map(|transform| &transform.xaos[..]).collect()
+    }
+
     pub fn make_particle<R: Rng>(&self, rng: &mut R) -> Particle {
         let mut particle = Particle::new();
         self.reset_particle(&mut particle, rng);
@@ -29,45 +104,105 @@ impl System {
     fn reset_particle<'a, R: Rng>(&'a self, particle: &'a mut Particle, rng: &mut R) -> &mut Particle {
         let ttl_range: Range<i32> = Range::new(1, self.ttl);
 
-        particle.point = self.reset_transformation.apply(&random::<Point>());
-        particle.color = random::<Number>();
+        particle.point = self.reset_transformation.apply(&rng.gen::<Point>());
+        particle.color = rng.gen::<Number>();
         particle.ttl = ttl_range.ind_sample(rng);
+        particle.last_transform = None;
+
+        // Let it get close to the attractor before it's output
+        for _ in 0..self.fuse {
+            self.transform_particle(particle, rng);
+        }
 
         particle
     }
 
-    fn animate_particle_mut<'a, R: Rng>(&'a self, particle: &'a mut Particle, transform: &'a Transform, rng: &mut R) -> &mut Particle {
-        transform.animate_mut(if particle.ttl <= 0 { self.reset_particle(particle, rng) } else { particle }).age()
+    /// Applies the next transform to the particle and returns its index.
+    fn transform_particle<R: Rng>(&self, particle: &mut Particle, rng: &mut R) -> usize {
+        let index = self.pick_transform(particle.last_transform, rng);
+
+        particle.last_transform = Some(index);
+        self.transforms[index].transform.animate_mut(particle, rng);
+
+        index
+    }
+
+    fn animate_particle_mut<R: Rng>(&self, particle: &mut Particle, rng: &mut R) -> usize {
+        let particle = if particle.ttl <= 0 { self.reset_particle(particle, rng) } else { particle };
+        let index = self.transform_particle(particle, rng);
+        particle.age();
+
+        index
+    }
+
+    /// Picks the index of the next transform, using the xaos row of the
+    /// previous one if there is one.
+    fn pick_transform<R: Rng>(&self, previous: Option<usize>, rng: &mut R) -> usize {
+        let selection = match previous.and_then(|index| self.xaos_selections[index].as_ref()) {
+            Some(selection) => selection,
+            None => &self.selection
+        };
+
+        selection.pick(rng)
     }
 
-    fn pick_transform<'a, R: Rng>(&'a self, rng: &mut R) -> &Transform {
-        let total_range = Range::new(0.0, self.max_range);
-        let value = total_range.ind_sample(rng);
+    fn is_within_bound(&self, point: &Point) -> bool {
+        // Also false for NaN
+        point.x.abs() <= self.bound && point.y.abs() <= self.bound
+    }
 
-        &(self.transforms.iter().find(|transform| value < transform.limit).unwrap().transform)
+    /// Moves the particle one iteration forward and returns what should be
+    /// output for it, which is `None` when either the particle or the output
+    /// of the final transform diverged (became NaN, infinite or went beyond
+    /// the bound). Only a diverged particle gets reset right away.
+    pub fn step<R: Rng>(&self, particle: &mut Particle, rng: &mut R) -> Option<Particle> {
+        self.step_with_transform(particle, rng).1
     }
 
-    pub fn step<R: Rng>(&self, particle: &mut Particle, rng: &mut R) -> Particle {
-        let transform = self.pick_transform(rng);
-        self.animate_particle_mut(particle, transform, rng);
-        self.final_transform.animate(particle)
+    /// Same as `step`, but also returns the index of the transform that was
+    /// applied, which a reset of the particle would overwrite.
+    pub fn step_with_transform<R: Rng>(&self, particle: &mut Particle, rng: &mut R) -> (usize, Option<Particle>) {
+        let index = self.animate_particle_mut(particle, rng);
+
+        if !self.is_within_bound(&particle.point) {
+            self.reset_particle(particle, rng);
+            return (index, None);
+        }
+
+        let output = self.final_transform.animate(particle, rng);
+        (index, if self.is_within_bound(&output.point) { Some(output) } else { None })
     }
 }
 
+/// Negative and NaN weights can't be picked, same as 0.
+fn non_negative(weight: f64) -> f64 {
+    if weight > 0.0 { weight } else { 0.0 }
+}
+
 pub struct SystemBuilder {
     transforms: Vec<(Transform, f64)>,
+    xaos: Vec<(usize, Vec<f64>)>,
     final_transform: Transform,
     ttl: i32,
-    reset_transformation: AffineTransformation
+    fuse: u32,
+    bound: Number,
+    reset_transformation: AffineTransformation,
+    seed: Option<u64>,
+    render_settings: RenderSettings
 }
 
 impl SystemBuilder {
     pub fn new() -> SystemBuilder {
         SystemBuilder {
             transforms: Vec::new(),
+            xaos: Vec::new(),
             final_transform: TransformBuilder::new().finalize(),
             ttl: 30,
-            reset_transformation: AffineTransformation::identity()
+            fuse: 20,
+            bound: 1e10,
+            reset_transformation: AffineTransformation::identity(),
+            seed: None,
+            render_settings: RenderSettings::new()
         }
     }
 
@@ -76,6 +211,16 @@ impl SystemBuilder {
         self
     }
 
+    /// Sets the xaos row of the transform added at `index`: the weight of
+    /// every transform picked right after it gets multiplied by the matching
+    /// entry, missing entries leave the weights as they are. Negative and NaN
+    /// entries count as 0, like in flam3. `finalize` fails if no transform was
+    /// added at `index`.
+    pub fn xaos(mut self, index: usize, row: Vec<f64>) -> SystemBuilder {
+        self.xaos.push((index, row));
+        self
+    }
+
     pub fn final_transform(mut self, transform: Transform) -> SystemBuilder {
         self.final_transform = transform;
         self
@@ -86,30 +231,162 @@ impl SystemBuilder {
         self
     }
 
+    pub fn fuse(mut self, fuse: u32) -> SystemBuilder {
+        self.fuse = fuse;
+        self
+    }
+
+    pub fn bound(mut self, bound: Number) -> SystemBuilder {
+        self.bound = bound;
+        self
+    }
+
     pub fn reset_transformation(mut self, transformation: AffineTransformation) -> SystemBuilder {
         self.reset_transformation = transformation;
         self
     }
 
-    pub fn finalize(self) -> System {
-        let starting_points: Vec<f64> = self.transforms.iter().fold(vec![0.0], |mut vec, &(_, weight)| {
-            let data = vec[vec.len()-1] + weight;
-            vec.push(data);
-            vec
-        });
+    pub fn seed(mut self, seed: u64) -> SystemBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn render_settings(mut self, render_settings: RenderSettings) -> SystemBuilder {
+        self.render_settings = render_settings;
+        self
+    }
+
+    /// Fails unless a transform has a positive weight and the ttl leaves room
+    /// for the random time to live of the particles, which is below it.
+    pub fn finalize(self) -> Result<System, &'static str> {
+        let weights: Vec<f64> = self.transforms.iter().map(|&(_, weight)| non_negative(weight)).collect();
+        let selection = match AliasTable::new(&weights) {
+            Some(selection) => selection,
+            None => return Err("System needs a transform with a positive weight")
+        };
+
+        if self.ttl < 2 {
+            return Err("System ttl must be at least 2");
+        }
 
-        let transforms: Vec<TransformWithLimit> = starting_points[1..]
-            .iter()
-            .zip(self.transforms.into_iter())
-            .map(|(limit, (transform, _))| TransformWithLimit { transform: transform, limit: *limit })
+        let mut rows = vec![Vec::new(); self.transforms.len()];
+        for (index, row) in self.xaos.into_iter() {
+            match rows.get_mut(index) {
+                Some(slot) => *slot = row,
+                None => return Err("System has xaos for a transform that doesn't exist")
+            }
+        }
+
+        // A row that leaves no transform to pick falls back to the plain weights
+        let xaos_selections = rows.iter().map(|row| {
+            if row.iter().all(|&value| value == 1.0) {
+                return None;
+            }
+
+            let row_weights: Vec<f64> = weights.iter().enumerate()
+                .map(|(j, weight)| weight * row.get(j).cloned().map_or(1.0, non_negative))
+                .collect();
+
+            AliasTable::new(&row_weights)
+        }).collect();
+
+        let transforms = self.transforms.into_iter().zip(rows.into_iter())
+            .map(|((transform, weight), xaos)| WeightedTransform { transform: transform, weight: weight, xaos: xaos })
             .collect();
 
-        System {
+        Ok(System {
             transforms: transforms,
+            selection: selection,
+            xaos_selections: xaos_selections,
             final_transform: self.final_transform,
             ttl: self.ttl,
-            max_range: starting_points[starting_points.len() - 1],
-            reset_transformation: self.reset_transformation
+            fuse: self.fuse,
+            bound: self.bound,
+            reset_transformation: self.reset_transformation,
+            seed: self.seed,
+            render_settings: self.render_settings
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AliasTable, System, SystemBuilder};
+    use types::transform::TransformBuilder;
+    use rand::{SeedableRng, XorShiftRng};
+
+    #[test]
+    fn alias_table_picks_in_proportion_to_weights() {
+        let table = AliasTable::new(&[1.0, 0.0, 3.0]).unwrap();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut counts = [0; 3];
+        let samples = 100000;
+
+        for _ in 0..samples {
+            counts[table.pick(&mut rng)] += 1;
         }
+
+        assert_eq!(counts[1], 0);
+        assert!((counts[0] as f64 / samples as f64 - 0.25).abs() < 0.01, "{:?}", counts);
+        assert!((counts[2] as f64 / samples as f64 - 0.75).abs() < 0.01, "{:?}", counts);
+    }
+
+    #[test]
+    fn alias_table_needs_a_positive_weight() {
+        assert!(AliasTable::new(&[]).is_none());
+        assert!(AliasTable::new(&[0.0, 0.0]).is_none());
+    }
+
+    fn builder(count: usize) -> SystemBuilder {
+        (0..count).fold(SystemBuilder::new(), |builder, _| builder.add_weighted_transform(TransformBuilder::new().finalize(), 1.0))
+    }
+
+    // How many times every transform gets picked right after `previous`
+    fn pick_counts(system: &System, previous: Option<usize>) -> Vec<usize> {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut counts = vec![0; system.transforms().len()];
+
+        for _ in 0..10000 {
+            counts[system.pick_transform(previous, &mut rng)] += 1;
+        }
+
+        counts
+    }
+
+    #[test]
+    fn xaos_rows_change_the_next_pick() {
+        let system = builder(2).xaos(0, vec![0.0, 1.0]).finalize().unwrap();
+
+        assert_eq!(pick_counts(&system, Some(0))[0], 0);
+        assert!(pick_counts(&system, Some(1)).iter().all(|&count| count > 0));
+        assert!(pick_counts(&system, None).iter().all(|&count| count > 0));
+    }
+
+    #[test]
+    fn negative_and_nan_xaos_entries_count_as_0() {
+        let system = builder(3).xaos(0, vec![-1.0, ::std::f64::NAN, 1.0]).finalize().unwrap();
+
+        assert_eq!(pick_counts(&system, Some(0)), vec![0, 0, 10000]);
+    }
+
+    #[test]
+    fn xaos_rows_without_anything_to_pick_fall_back_to_the_weights() {
+        let system = builder(2).xaos(0, vec![0.0, 0.0]).finalize().unwrap();
+
+        assert!(pick_counts(&system, Some(0)).iter().all(|&count| count > 0));
+    }
+
+    #[test]
+    fn xaos_needs_an_existing_transform() {
+        assert!(builder(2).xaos(2, vec![1.0, 1.0]).finalize().is_err());
+    }
+
+    #[test]
+    fn systems_need_something_to_pick_and_room_for_a_ttl() {
+        assert!(SystemBuilder::new().finalize().is_err());
+        assert!(SystemBuilder::new().add_weighted_transform(TransformBuilder::new().finalize(), 0.0).finalize().is_err());
+        assert!(SystemBuilder::new().add_weighted_transform(TransformBuilder::new().finalize(), -1.0).finalize().is_err());
+        assert!(SystemBuilder::new().add_weighted_transform(TransformBuilder::new().finalize(), 1.0).ttl(1).finalize().is_err());
+        assert!(SystemBuilder::new().add_weighted_transform(TransformBuilder::new().finalize(), 1.0).ttl(2).finalize().is_ok());
     }
 }
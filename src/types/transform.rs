@@ -1,8 +1,9 @@
 use std::boxed::Box;
 use types::{Number, Point, Particle, Applicable, Variation, AffineTransformation};
+use rand::Rng;
 use types::coloring_method::{ColoringMethod, SingleColor};
 
-type WeightedVariation = (Box<Variation>, Number, Number);
+pub type WeightedVariation = (Box<Variation>, Number, Number);
 
 #[derive(Debug)]
 pub struct Transform {
@@ -13,43 +14,59 @@ pub struct Transform {
 }
 
 impl Transform {
-    pub fn animate(&self, particle: &Particle) -> Particle {
-        let point = self.apply(&particle.point);
-        let color = self.coloring_method.color(&particle, &point);
+    pub fn pre(&self) -> &AffineTransformation {
+        &self.pre
+    }
 
-        Particle {
-            point: point,
-            color: color,
-            ttl: particle.ttl
-        }
+    pub fn post(&self) -> &AffineTransformation {
+        &self.post
     }
 
-    pub fn animate_mut<'a>(&'a self, particle: &'a mut Particle) -> &mut Particle {
-        let point = self.apply(&particle.point);
-        let color = self.coloring_method.color(&particle, &point);
+    pub fn variations(&self) -> &[WeightedVariation] {
+        &self.variations
+    }
 
-        particle.point = point;
-        particle.color = color;
-        particle
+    pub fn coloring_method(&self) -> &ColoringMethod {
+        &*self.coloring_method
     }
-}
 
-impl Applicable for Transform {
-    fn apply(&self, point: &Point) -> Point {
+    pub fn apply<R: Rng>(&self, point: &Point, rng: &mut R) -> Point {
         let initial = self.pre.apply(point);
 
         let after_variations = if self.variations.is_empty() {
             initial
         } else {
-            self.variations.iter()
-                .map(|&(ref variation, weight_x, weight_y)| {
-                    variation.apply(&initial, &self.pre) * (weight_x, weight_y)
-                })
-                .fold(Point::new(), |result, p| { result + p })
+            let mut result = Point::new();
+            for &(ref variation, weight_x, weight_y) in self.variations.iter() {
+                result = result + variation.apply(&initial, &self.pre, rng) * (weight_x, weight_y);
+            }
+
+            result
         };
 
         self.post.apply(&after_variations)
     }
+
+    pub fn animate<R: Rng>(&self, particle: &Particle, rng: &mut R) -> Particle {
+        let point = self.apply(&particle.point, rng);
+        let color = self.coloring_method.color(&particle, &point);
+
+        Particle {
+            point: point,
+            color: color,
+            ttl: particle.ttl,
+            last_transform: particle.last_transform
+        }
+    }
+
+    pub fn animate_mut<'a, R: Rng>(&'a self, particle: &'a mut Particle, rng: &mut R) -> &mut Particle {
+        let point = self.apply(&particle.point, rng);
+        let color = self.coloring_method.color(&particle, &point);
+
+        particle.point = point;
+        particle.color = color;
+        particle
+    }
 }
 
 pub struct TransformBuilder {
@@ -0,0 +1,1693 @@
+//! The variations of flam3, following "The Fractal Flame Algorithm" and
+//! flam3's `variations.c`. Parameters are taken in the order flam3 lists their
+//! attributes, the variation's weight gets applied to the result by the
+//! transform.
+
+use std::f64::consts::PI;
+use rand::Rng;
+use types::{Point, Number, Variation, AffineTransformation};
+
+const EPS: Number = 1e-10;
+
+fn sum_sq(point: &Point) -> Number {
+    point.x * point.x + point.y * point.y
+}
+
+fn length(point: &Point) -> Number {
+    sum_sq(point).sqrt()
+}
+
+// flam3 measures this one from the y axis
+fn angle(point: &Point) -> Number {
+    point.x.atan2(point.y)
+}
+
+fn angle_yx(point: &Point) -> Number {
+    point.y.atan2(point.x)
+}
+
+// Roughly normally distributed between -2 and 2, like flam3's
+fn gaussian(rng: &mut Rng) -> Number {
+    rng.next_f64() + rng.next_f64() + rng.next_f64() + rng.next_f64() - 2.0
+}
+
+fn wrap(value: Number, size: Number) -> Number {
+    if value > size {
+        -size + (value + size) % (2.0 * size)
+    } else if value < -size {
+        size - (size - value) % (2.0 * size)
+    } else {
+        value
+    }
+}
+
+fn separate(value: Number, amount: Number, inside: Number) -> Number {
+    let distance = (value * value + amount * amount).sqrt();
+
+    if value > 0.0 {
+        distance - value * inside
+    } else {
+        -(distance + value * inside)
+    }
+}
+
+fn wedge(a: Number, angle: Number, count: Number) -> Number {
+    let c = ((count * a + PI) / (2.0 * PI)).floor();
+    a * (1.0 - angle * count / (2.0 * PI)) + c * angle
+}
+
+
+#[derive(Debug)]
+pub struct Sinusoidal;
+
+impl Variation for Sinusoidal {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.sin(), point.y.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Sinusoidal"
+    }
+}
+
+#[derive(Debug)]
+pub struct Spherical;
+
+impl Variation for Spherical {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        *point * (1.0 / (sum_sq(point) + EPS))
+    }
+
+    fn name(&self) -> &str {
+        "Spherical"
+    }
+}
+
+#[derive(Debug)]
+pub struct Swirl;
+
+impl Variation for Swirl {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let (sin, cos) = sum_sq(point).sin_cos();
+        Point::from_xy(point.x * sin - point.y * cos, point.x * cos + point.y * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Swirl"
+    }
+}
+
+#[derive(Debug)]
+pub struct Horseshoe;
+
+impl Variation for Horseshoe {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = 1.0 / (length(point) + EPS);
+        Point::from_xy((point.x - point.y) * (point.x + point.y) * r, 2.0 * point.x * point.y * r)
+    }
+
+    fn name(&self) -> &str {
+        "Horseshoe"
+    }
+}
+
+#[derive(Debug)]
+pub struct Polar;
+
+impl Variation for Polar {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(angle(point) / PI, length(point) - 1.0)
+    }
+
+    fn name(&self) -> &str {
+        "Polar"
+    }
+}
+
+#[derive(Debug)]
+pub struct Handkerchief;
+
+impl Variation for Handkerchief {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let (a, r) = (angle(point), length(point));
+        Point::from_xy(r * (a + r).sin(), r * (a - r).cos())
+    }
+
+    fn name(&self) -> &str {
+        "Handkerchief"
+    }
+}
+
+#[derive(Debug)]
+pub struct Heart;
+
+impl Variation for Heart {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = length(point);
+        let (sin, cos) = (r * angle(point)).sin_cos();
+        Point::from_xy(r * sin, -r * cos)
+    }
+
+    fn name(&self) -> &str {
+        "Heart"
+    }
+}
+
+#[derive(Debug)]
+pub struct Disc;
+
+impl Variation for Disc {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let a = angle(point) / PI;
+        let (sin, cos) = (PI * length(point)).sin_cos();
+        Point::from_xy(sin * a, cos * a)
+    }
+
+    fn name(&self) -> &str {
+        "Disc"
+    }
+}
+
+#[derive(Debug)]
+pub struct Spiral;
+
+impl Variation for Spiral {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = length(point) + EPS;
+        let (sin_a, cos_a) = angle(point).sin_cos();
+        let (sin_r, cos_r) = r.sin_cos();
+        Point::from_xy((cos_a + sin_r) / r, (sin_a - cos_r) / r)
+    }
+
+    fn name(&self) -> &str {
+        "Spiral"
+    }
+}
+
+#[derive(Debug)]
+pub struct Hyperbolic;
+
+impl Variation for Hyperbolic {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = length(point) + EPS;
+        let (sin, cos) = angle(point).sin_cos();
+        Point::from_xy(sin / r, cos * r)
+    }
+
+    fn name(&self) -> &str {
+        "Hyperbolic"
+    }
+}
+
+#[derive(Debug)]
+pub struct Diamond;
+
+impl Variation for Diamond {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let (sin_a, cos_a) = angle(point).sin_cos();
+        let (sin_r, cos_r) = length(point).sin_cos();
+        Point::from_xy(sin_a * cos_r, cos_a * sin_r)
+    }
+
+    fn name(&self) -> &str {
+        "Diamond"
+    }
+}
+
+#[derive(Debug)]
+pub struct Ex;
+
+impl Variation for Ex {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let (a, r) = (angle(point), length(point));
+        let m0 = (a + r).sin().powi(3) * r;
+        let m1 = (a - r).cos().powi(3) * r;
+        Point::from_xy(m0 + m1, m0 - m1)
+    }
+
+    fn name(&self) -> &str {
+        "Ex"
+    }
+}
+
+/// Picks one of the two square roots at random.
+#[derive(Debug)]
+pub struct Julia;
+
+impl Variation for Julia {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let a = angle(point) / 2.0 + if rng.next_f64() < 0.5 { PI } else { 0.0 };
+        let r = length(point).sqrt();
+        Point::from_xy(r * a.cos(), r * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Julia"
+    }
+}
+
+#[derive(Debug)]
+pub struct Bent;
+
+impl Variation for Bent {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(
+            if point.x < 0.0 { point.x * 2.0 } else { point.x },
+            if point.y < 0.0 { point.y / 2.0 } else { point.y }
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Bent"
+    }
+}
+
+/// Uses the `b`, `c`, `e` and `f` coefficients of the transformation.
+#[derive(Debug)]
+pub struct Waves;
+
+impl Variation for Waves {
+    fn apply(&self, point: &Point, transformation: &AffineTransformation, _: &mut Rng) -> Point {
+        let coefficients = transformation.coefficients();
+        let (b, c, e, f) = (coefficients[1], coefficients[2], coefficients[4], coefficients[5]);
+        Point::from_xy(
+            point.x + b * (point.y / (c * c + EPS)).sin(),
+            point.y + e * (point.x / (f * f + EPS)).sin()
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Waves"
+    }
+}
+
+#[derive(Debug)]
+pub struct Fisheye;
+
+impl Variation for Fisheye {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = 2.0 / (length(point) + 1.0);
+        Point::from_xy(r * point.y, r * point.x)
+    }
+
+    fn name(&self) -> &str {
+        "Fisheye"
+    }
+}
+
+/// Uses the translation of the transformation.
+#[derive(Debug)]
+pub struct Popcorn;
+
+impl Variation for Popcorn {
+    fn apply(&self, point: &Point, transformation: &AffineTransformation, _: &mut Rng) -> Point {
+        let coefficients = transformation.coefficients();
+        let (c, f) = (coefficients[2], coefficients[5]);
+        Point::from_xy(
+            point.x + c * (3.0 * point.y).tan().sin(),
+            point.y + f * (3.0 * point.x).tan().sin()
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Popcorn"
+    }
+}
+
+#[derive(Debug)]
+pub struct Exponential;
+
+impl Variation for Exponential {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = (point.x - 1.0).exp();
+        let (sin, cos) = (PI * point.y).sin_cos();
+        Point::from_xy(r * cos, r * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Exponential"
+    }
+}
+
+#[derive(Debug)]
+pub struct Power;
+
+impl Variation for Power {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let (sin, cos) = angle(point).sin_cos();
+        let r = length(point).powf(sin);
+        Point::from_xy(r * cos, r * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Power"
+    }
+}
+
+#[derive(Debug)]
+pub struct Cosine;
+
+impl Variation for Cosine {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let (sin, cos) = (PI * point.x).sin_cos();
+        Point::from_xy(cos * point.y.cosh(), -sin * point.y.sinh())
+    }
+
+    fn name(&self) -> &str {
+        "Cosine"
+    }
+}
+
+/// Uses the `c` coefficient of the transformation as the ring size.
+#[derive(Debug)]
+pub struct Rings;
+
+impl Variation for Rings {
+    fn apply(&self, point: &Point, transformation: &AffineTransformation, _: &mut Rng) -> Point {
+        let c = transformation.coefficients()[2];
+        let dx = c * c + EPS;
+        let r = length(point);
+        let r = (r + dx) % (2.0 * dx) - dx + r * (1.0 - dx);
+        let (sin, cos) = angle(point).sin_cos();
+        Point::from_xy(r * cos, r * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Rings"
+    }
+}
+
+/// Uses the translation of the transformation.
+#[derive(Debug)]
+pub struct Fan;
+
+impl Variation for Fan {
+    fn apply(&self, point: &Point, transformation: &AffineTransformation, _: &mut Rng) -> Point {
+        let coefficients = transformation.coefficients();
+        let (c, f) = (coefficients[2], coefficients[5]);
+        let dx = PI * (c * c + EPS);
+        let mut a = angle(point);
+        a += if (a + f) % dx > dx / 2.0 { -dx / 2.0 } else { dx / 2.0 };
+        let r = length(point);
+        Point::from_xy(r * a.cos(), r * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Fan"
+    }
+}
+
+/// Takes `high`, `low` and `waves`.
+#[derive(Debug)]
+pub struct Blob(pub Number, pub Number, pub Number);
+
+impl Variation for Blob {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Blob(high, low, waves) = *self;
+        let a = angle(point);
+        let r = length(point) * (low + (high - low) * (0.5 + 0.5 * (waves * a).sin()));
+        Point::from_xy(a.sin() * r, a.cos() * r)
+    }
+
+    fn name(&self) -> &str {
+        "Blob"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2]
+    }
+}
+
+/// Takes `a`, `b`, `c` and `d`.
+#[derive(Debug)]
+pub struct Pdj(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Pdj {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Pdj(a, b, c, d) = *self;
+        Point::from_xy(
+            (a * point.y).sin() - (b * point.x).cos(),
+            (c * point.x).sin() - (d * point.y).cos()
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Pdj"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `x` and `y`.
+#[derive(Debug)]
+pub struct Fan2(pub Number, pub Number);
+
+impl Variation for Fan2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Fan2(x, y) = *self;
+        let dx = PI * (x * x + EPS);
+        let mut a = angle(point);
+        let t = a + y - dx * ((a + y) / dx).trunc();
+        a += if t > dx / 2.0 { -dx / 2.0 } else { dx / 2.0 };
+        let r = length(point);
+        Point::from_xy(r * a.sin(), r * a.cos())
+    }
+
+    fn name(&self) -> &str {
+        "Fan2"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `value`.
+#[derive(Debug)]
+pub struct Rings2(pub Number);
+
+impl Variation for Rings2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Rings2(value) = *self;
+        let dx = value * value + EPS;
+        let r = length(point);
+        let r = r - 2.0 * dx * ((r + dx) / (2.0 * dx)).trunc() + r * (1.0 - dx);
+        let (sin, cos) = angle(point).sin_cos();
+        Point::from_xy(sin * r, cos * r)
+    }
+
+    fn name(&self) -> &str {
+        "Rings2"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0]
+    }
+}
+
+#[derive(Debug)]
+pub struct Eyefish;
+
+impl Variation for Eyefish {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        *point * (2.0 / (length(point) + 1.0))
+    }
+
+    fn name(&self) -> &str {
+        "Eyefish"
+    }
+}
+
+#[derive(Debug)]
+pub struct Bubble;
+
+impl Variation for Bubble {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        *point * (1.0 / (sum_sq(point) / 4.0 + 1.0))
+    }
+
+    fn name(&self) -> &str {
+        "Bubble"
+    }
+}
+
+#[derive(Debug)]
+pub struct Cylinder;
+
+impl Variation for Cylinder {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.sin(), point.y)
+    }
+
+    fn name(&self) -> &str {
+        "Cylinder"
+    }
+}
+
+/// Takes `angle` and `distance`.
+#[derive(Debug)]
+pub struct Perspective(pub Number, pub Number);
+
+impl Variation for Perspective {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Perspective(angle, distance) = *self;
+        let (sin, cos) = (angle * PI / 2.0).sin_cos();
+        let t = 1.0 / (distance - point.y * sin);
+        Point::from_xy(distance * point.x * t, distance * cos * point.y * t)
+    }
+
+    fn name(&self) -> &str {
+        "Perspective"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+#[derive(Debug)]
+pub struct Noise;
+
+impl Variation for Noise {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let (sin, cos) = (rng.next_f64() * 2.0 * PI).sin_cos();
+        let r = rng.next_f64();
+        Point::from_xy(point.x * r * cos, point.y * r * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Noise"
+    }
+}
+
+/// Takes `power` and `distance`.
+#[derive(Debug)]
+pub struct Julian(pub Number, pub Number);
+
+impl Variation for Julian {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Julian(power, distance) = *self;
+        let branch = (power.abs() * rng.next_f64()).trunc();
+        let a = (angle_yx(point) + 2.0 * PI * branch) / power;
+        let r = sum_sq(point).powf(distance / power / 2.0);
+        Point::from_xy(r * a.cos(), r * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Julian"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `power` and `distance`.
+#[derive(Debug)]
+pub struct Juliascope(pub Number, pub Number);
+
+impl Variation for Juliascope {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Juliascope(power, distance) = *self;
+        let branch = (power.abs() * rng.next_f64()).trunc();
+        let a = if branch % 2.0 == 0.0 {
+            (2.0 * PI * branch + angle_yx(point)) / power
+        } else {
+            (2.0 * PI * branch - angle_yx(point)) / power
+        };
+        let r = sum_sq(point).powf(distance / power / 2.0);
+        Point::from_xy(r * a.cos(), r * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Juliascope"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+#[derive(Debug)]
+pub struct Blur;
+
+impl Variation for Blur {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let (sin, cos) = (rng.next_f64() * 2.0 * PI).sin_cos();
+        let r = rng.next_f64();
+        Point::from_xy(r * cos, r * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Blur"
+    }
+}
+
+#[derive(Debug)]
+pub struct GaussianBlur;
+
+impl Variation for GaussianBlur {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let (sin, cos) = (rng.next_f64() * 2.0 * PI).sin_cos();
+        let r = gaussian(rng);
+        Point::from_xy(r * cos, r * sin)
+    }
+
+    fn name(&self) -> &str {
+        "GaussianBlur"
+    }
+}
+
+/// Takes `angle`.
+/// flam3 scales the blur itself by the variation's weight, here the weight
+/// only scales the result like for every other variation, so flam3's
+/// `radial_blur` isn't imported as this.
+#[derive(Debug)]
+pub struct RadialBlur(pub Number);
+
+impl Variation for RadialBlur {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let RadialBlur(angle) = *self;
+        let (spin, zoom) = (angle * PI / 2.0).sin_cos();
+        let amount = gaussian(rng);
+        let a = angle_yx(point) + spin * amount;
+        let r = length(point);
+        let z = zoom * amount - 1.0;
+        Point::from_xy(r * a.cos() + z * point.x, r * a.sin() + z * point.y)
+    }
+
+    fn name(&self) -> &str {
+        "RadialBlur"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0]
+    }
+}
+
+/// Takes `slices`, `rotation` and `thickness`.
+#[derive(Debug)]
+pub struct Pie(pub Number, pub Number, pub Number);
+
+impl Variation for Pie {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Pie(slices, rotation, thickness) = *self;
+        let slice = (rng.next_f64() * slices + 0.5).trunc();
+        let a = rotation + 2.0 * PI * (slice + rng.next_f64() * thickness) / slices;
+        let r = rng.next_f64();
+        Point::from_xy(r * a.cos(), r * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Pie"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2]
+    }
+}
+
+/// Takes `sides`, `power`, `circle` and `corners`.
+#[derive(Debug)]
+pub struct Ngon(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Ngon {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Ngon(sides, power, circle, corners) = *self;
+        let r = sum_sq(point).powf(power / 2.0);
+        let b = 2.0 * PI / sides;
+        let mut phi = angle_yx(point);
+        phi -= b * (phi / b).floor();
+        if phi > b / 2.0 {
+            phi -= b;
+        }
+
+        let amp = (corners * (1.0 / (phi.cos() + EPS) - 1.0) + circle) / (r + EPS);
+        *point * amp
+    }
+
+    fn name(&self) -> &str {
+        "Ngon"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `c1` and `c2`.
+#[derive(Debug)]
+pub struct Curl(pub Number, pub Number);
+
+impl Variation for Curl {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Curl(c1, c2) = *self;
+        let re = 1.0 + c1 * point.x + c2 * (point.x * point.x - point.y * point.y);
+        let im = c1 * point.y + 2.0 * c2 * point.x * point.y;
+        let r = 1.0 / (re * re + im * im);
+        Point::from_xy((point.x * re + point.y * im) * r, (point.y * re - point.x * im) * r)
+    }
+
+    fn name(&self) -> &str {
+        "Curl"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `x` and `y`.
+#[derive(Debug)]
+pub struct Rectangles(pub Number, pub Number);
+
+impl Variation for Rectangles {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Rectangles(x, y) = *self;
+        Point::from_xy(
+            if x == 0.0 { point.x } else { (2.0 * (point.x / x).floor() + 1.0) * x - point.x },
+            if y == 0.0 { point.y } else { (2.0 * (point.y / y).floor() + 1.0) * y - point.y }
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Rectangles"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+#[derive(Debug)]
+pub struct Tangent;
+
+impl Variation for Tangent {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.sin() / point.y.cos(), point.y.tan())
+    }
+
+    fn name(&self) -> &str {
+        "Tangent"
+    }
+}
+
+#[derive(Debug)]
+pub struct Square;
+
+impl Variation for Square {
+    fn apply(&self, _: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        Point::from_xy(rng.next_f64() - 0.5, rng.next_f64() - 0.5)
+    }
+
+    fn name(&self) -> &str {
+        "Square"
+    }
+}
+
+#[derive(Debug)]
+pub struct Cross;
+
+impl Variation for Cross {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let s = point.x * point.x - point.y * point.y;
+        *point * (1.0 / (s * s + EPS)).sqrt()
+    }
+
+    fn name(&self) -> &str {
+        "Cross"
+    }
+}
+
+/// Takes `rotation` and `twist`.
+#[derive(Debug)]
+pub struct Disc2(pub Number, pub Number);
+
+impl Variation for Disc2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Disc2(rotation, twist) = *self;
+        let (mut sin_add, mut cos_add) = twist.sin_cos();
+        cos_add -= 1.0;
+
+        let k = if twist > 2.0 * PI {
+            1.0 + twist - 2.0 * PI
+        } else if twist < -2.0 * PI {
+            1.0 + twist + 2.0 * PI
+        } else {
+            1.0
+        };
+        sin_add *= k;
+        cos_add *= k;
+
+        let (sin, cos) = (rotation * PI * (point.x + point.y)).sin_cos();
+        let r = angle(point) / PI;
+        Point::from_xy((sin + cos_add) * r, (cos + sin_add) * r)
+    }
+
+    fn name(&self) -> &str {
+        "Disc2"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `rnd`, `m`, `n1`, `n2`, `n3` and `holes`.
+#[derive(Debug)]
+pub struct SuperShape(pub Number, pub Number, pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for SuperShape {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let SuperShape(rnd, m, n1, n2, n3, holes) = *self;
+        let theta = m / 4.0 * angle_yx(point) + PI / 4.0;
+        let t = theta.cos().abs().powf(n2) + theta.sin().abs().powf(n3);
+        let r = length(point);
+        let amount = ((rnd * rng.next_f64() + (1.0 - rnd) * r) - holes) * t.powf(-1.0 / n1) / r;
+        *point * amount
+    }
+
+    fn name(&self) -> &str {
+        "SuperShape"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3, self.4, self.5]
+    }
+}
+
+/// Takes `petals` and `holes`.
+#[derive(Debug)]
+pub struct Flower(pub Number, pub Number);
+
+impl Variation for Flower {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Flower(petals, holes) = *self;
+        let r = (rng.next_f64() - holes) * (petals * angle_yx(point)).cos() / length(point);
+        *point * r
+    }
+
+    fn name(&self) -> &str {
+        "Flower"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `eccentricity` and `holes`.
+#[derive(Debug)]
+pub struct Conic(pub Number, pub Number);
+
+impl Variation for Conic {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Conic(eccentricity, holes) = *self;
+        let r = length(point);
+        let amount = (rng.next_f64() - holes) * eccentricity / (1.0 + eccentricity * point.x / r) / r;
+        *point * amount
+    }
+
+    fn name(&self) -> &str {
+        "Conic"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `height` and `width`.
+#[derive(Debug)]
+pub struct Parabola(pub Number, pub Number);
+
+impl Variation for Parabola {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Parabola(height, width) = *self;
+        let (sin, cos) = length(point).sin_cos();
+        Point::from_xy(height * sin * sin * rng.next_f64(), width * cos * rng.next_f64())
+    }
+
+    fn name(&self) -> &str {
+        "Parabola"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `x` and `y`.
+#[derive(Debug)]
+pub struct Bent2(pub Number, pub Number);
+
+impl Variation for Bent2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Bent2(x, y) = *self;
+        Point::from_xy(
+            if point.x < 0.0 { point.x * x } else { point.x },
+            if point.y < 0.0 { point.y * y } else { point.y }
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Bent2"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `shift`.
+/// Maps points where the result is undefined to the origin.
+#[derive(Debug)]
+pub struct Bipolar(pub Number);
+
+impl Variation for Bipolar {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Bipolar(shift) = *self;
+        let r2 = sum_sq(point);
+        let t = r2 + 1.0;
+        let x2 = 2.0 * point.x;
+        let mut y = 0.5 * (2.0 * point.y).atan2(r2 - 1.0) - PI / 2.0 * shift;
+
+        if y > PI / 2.0 {
+            y = -PI / 2.0 + (y + PI / 2.0) % PI;
+        } else if y < -PI / 2.0 {
+            y = PI / 2.0 - (PI / 2.0 - y) % PI;
+        }
+
+        if t - x2 == 0.0 || (t + x2) / (t - x2) <= 0.0 {
+            return Point::new();
+        }
+
+        Point::from_xy(0.25 * 2.0 / PI * ((t + x2) / (t - x2)).ln(), 2.0 / PI * y)
+    }
+
+    fn name(&self) -> &str {
+        "Bipolar"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0]
+    }
+}
+
+#[derive(Debug)]
+pub struct Boarders;
+
+impl Variation for Boarders {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let (round_x, round_y) = (point.x.round(), point.y.round());
+        let (offset_x, offset_y) = (point.x - round_x, point.y - round_y);
+        let (x, y) = (offset_x * 0.5 + round_x, offset_y * 0.5 + round_y);
+
+        // A quarter of the points stay in the shrunken cells, the rest go to their borders
+        if rng.next_f64() >= 0.75 {
+            Point::from_xy(x, y)
+        } else if offset_x.abs() >= offset_y.abs() {
+            let side = if offset_x >= 0.0 { 0.25 } else { -0.25 };
+            Point::from_xy(x + side, y + side * offset_y / offset_x)
+        } else {
+            let side = if offset_y >= 0.0 { 0.25 } else { -0.25 };
+            Point::from_xy(x + side * offset_x / offset_y, y + side)
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Boarders"
+    }
+}
+
+#[derive(Debug)]
+pub struct Butterfly;
+
+impl Variation for Butterfly {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let y2 = 2.0 * point.y;
+        let r = 1.3029400317411197908970256609023 * ((point.x * point.y).abs() / (EPS + point.x * point.x + y2 * y2)).sqrt();
+        Point::from_xy(r * point.x, r * y2)
+    }
+
+    fn name(&self) -> &str {
+        "Butterfly"
+    }
+}
+
+/// Takes `size`.
+#[derive(Debug)]
+pub struct Cell(pub Number);
+
+impl Variation for Cell {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Cell(size) = *self;
+        let mut x = (point.x / size).floor();
+        let mut y = (point.y / size).floor();
+        let dx = point.x - x * size;
+        let dy = point.y - y * size;
+
+        x = if x >= 0.0 { x * 2.0 } else { -(2.0 * x + 1.0) };
+        y = if y >= 0.0 { y * 2.0 } else { -(2.0 * y + 1.0) };
+
+        Point::from_xy(dx + x * size, -(dy + y * size))
+    }
+
+    fn name(&self) -> &str {
+        "Cell"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0]
+    }
+}
+
+/// Takes `real`, `imaginary` and `power`.
+#[derive(Debug)]
+pub struct Cpow(pub Number, pub Number, pub Number);
+
+impl Variation for Cpow {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let Cpow(real, imaginary, power) = *self;
+        let phi = angle_yx(point);
+        let ln_r = 0.5 * sum_sq(point).ln();
+        let (c, d) = (real / power, imaginary / power);
+        let branch = (power * rng.next_f64()).floor();
+        let a = c * phi + d * ln_r + 2.0 * PI / power * branch;
+        let m = (c * ln_r - d * phi).exp();
+        Point::from_xy(m * a.cos(), m * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Cpow"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2]
+    }
+}
+
+/// Takes `x_amplitude`, `y_amplitude`, `x_length` and `y_length`.
+#[derive(Debug)]
+pub struct Curve(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Curve {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Curve(x_amplitude, y_amplitude, x_length, y_length) = *self;
+        let x_length = (x_length * x_length).max(1e-20);
+        let y_length = (y_length * y_length).max(1e-20);
+        Point::from_xy(
+            point.x + x_amplitude * (-point.y * point.y / x_length).exp(),
+            point.y + y_amplitude * (-point.x * point.x / y_length).exp()
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Curve"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+#[derive(Debug)]
+pub struct Edisc;
+
+impl Variation for Edisc {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let t = sum_sq(point) + 1.0;
+        let x2 = 2.0 * point.x;
+        let x_max = ((t + x2).sqrt() + (t - x2).sqrt()) * 0.5;
+        let a1 = (x_max + (x_max - 1.0).sqrt()).ln();
+        let a2 = -(point.x / x_max).acos();
+
+        let (mut sin, cos) = a1.sin_cos();
+        if point.y > 0.0 {
+            sin = -sin;
+        }
+
+        Point::from_xy(a2.cosh() * cos / 11.57034632, a2.sinh() * sin / 11.57034632)
+    }
+
+    fn name(&self) -> &str {
+        "Edisc"
+    }
+}
+
+#[derive(Debug)]
+pub struct Elliptic;
+
+impl Variation for Elliptic {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let t = sum_sq(point) + 1.0;
+        let x2 = 2.0 * point.x;
+        let x_max = 0.5 * ((t + x2).sqrt() + (t - x2).sqrt());
+        let a = point.x / x_max;
+        let b = (1.0 - a * a).max(0.0).sqrt();
+        let s = (x_max - 1.0).max(0.0).sqrt();
+        let y = (x_max + s).ln() / (PI / 2.0);
+
+        Point::from_xy(a.atan2(b) / (PI / 2.0), if point.y > 0.0 { y } else { -y })
+    }
+
+    fn name(&self) -> &str {
+        "Elliptic"
+    }
+}
+
+/// Takes `beta`.
+#[derive(Debug)]
+pub struct Escher(pub Number);
+
+impl Variation for Escher {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Escher(beta) = *self;
+        let a = angle_yx(point);
+        let ln_r = 0.5 * sum_sq(point).ln();
+        let (sin, cos) = beta.sin_cos();
+        let (c, d) = (0.5 * (1.0 + cos), 0.5 * sin);
+        let m = (c * ln_r - d * a).exp();
+        let n = c * a + d * ln_r;
+        Point::from_xy(m * n.cos(), m * n.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Escher"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0]
+    }
+}
+
+#[derive(Debug)]
+pub struct Foci;
+
+impl Variation for Foci {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let exp_x = point.x.exp() * 0.5;
+        let exp_nx = 0.25 / exp_x;
+        let (sin, cos) = point.y.sin_cos();
+        let t = 1.0 / (exp_x + exp_nx - cos);
+        Point::from_xy(t * (exp_x - exp_nx), t * sin)
+    }
+
+    fn name(&self) -> &str {
+        "Foci"
+    }
+}
+
+/// Takes `x` and `y`.
+#[derive(Debug)]
+pub struct Modulus(pub Number, pub Number);
+
+impl Variation for Modulus {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Modulus(x, y) = *self;
+        Point::from_xy(wrap(point.x, x), wrap(point.y, y))
+    }
+
+    fn name(&self) -> &str {
+        "Modulus"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `separation`, `frequency`, `amplitude` and `damping`.
+#[derive(Debug)]
+pub struct Oscilloscope(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Oscilloscope {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Oscilloscope(separation, frequency, amplitude, damping) = *self;
+        let wave = (2.0 * PI * frequency * point.x).cos();
+        let t = if damping.abs() <= EPS {
+            amplitude * wave + separation
+        } else {
+            amplitude * (-point.x.abs() * damping).exp() * wave + separation
+        };
+
+        Point::from_xy(point.x, if point.y.abs() <= t { -point.y } else { point.y })
+    }
+
+    fn name(&self) -> &str {
+        "Oscilloscope"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+#[derive(Debug)]
+pub struct Polar2;
+
+impl Variation for Polar2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(angle(point) / PI, sum_sq(point).ln() / (2.0 * PI))
+    }
+
+    fn name(&self) -> &str {
+        "Polar2"
+    }
+}
+
+/// Takes `x`, `y` and `c`.
+#[derive(Debug)]
+pub struct Popcorn2(pub Number, pub Number, pub Number);
+
+impl Variation for Popcorn2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Popcorn2(x, y, c) = *self;
+        Point::from_xy(
+            point.x + x * (point.y * c).tan().sin(),
+            point.y + y * (point.x * c).tan().sin()
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Popcorn2"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2]
+    }
+}
+
+/// Takes `x`, `x_inside`, `y` and `y_inside`.
+#[derive(Debug)]
+pub struct Separation(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Separation {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Separation(x, x_inside, y, y_inside) = *self;
+        Point::from_xy(separate(point.x, x, x_inside), separate(point.y, y, y_inside))
+    }
+
+    fn name(&self) -> &str {
+        "Separation"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `x_size` and `y_size`.
+#[derive(Debug)]
+pub struct Split(pub Number, pub Number);
+
+impl Variation for Split {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Split(x_size, y_size) = *self;
+        Point::from_xy(
+            if (point.y * y_size * PI).cos() >= 0.0 { point.x } else { -point.x },
+            if (point.x * x_size * PI).cos() >= 0.0 { point.y } else { -point.y }
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Split"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `x` and `y`.
+#[derive(Debug)]
+pub struct Splits(pub Number, pub Number);
+
+impl Variation for Splits {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Splits(x, y) = *self;
+        Point::from_xy(
+            if point.x >= 0.0 { point.x + x } else { point.x - x },
+            if point.y >= 0.0 { point.y + y } else { point.y - y }
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Splits"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `space` and `warp`.
+#[derive(Debug)]
+pub struct Stripes(pub Number, pub Number);
+
+impl Variation for Stripes {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Stripes(space, warp) = *self;
+        let round_x = (point.x + 0.5).floor();
+        let offset_x = point.x - round_x;
+        Point::from_xy(offset_x * (1.0 - space) + round_x, point.y + offset_x * offset_x * warp)
+    }
+
+    fn name(&self) -> &str {
+        "Stripes"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1]
+    }
+}
+
+/// Takes `angle`, `hole`, `count` and `swirl`.
+#[derive(Debug)]
+pub struct Wedge(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Wedge {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Wedge(angle, hole, count, swirl) = *self;
+        let r = length(point);
+        let a = wedge(angle_yx(point) + swirl * r, angle, count);
+        Point::from_xy((r + hole) * a.cos(), (r + hole) * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Wedge"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `angle`, `count`, `power` and `distance`.
+#[derive(Debug)]
+pub struct WedgeJulia(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for WedgeJulia {
+    fn apply(&self, point: &Point, _: &AffineTransformation, rng: &mut Rng) -> Point {
+        let WedgeJulia(angle, count, power, distance) = *self;
+        let r = sum_sq(point).powf(distance / power / 2.0);
+        let branch = (power.abs() * rng.next_f64()).trunc();
+        let a = wedge((angle_yx(point) + 2.0 * PI * branch) / power, angle, count);
+        Point::from_xy(r * a.cos(), r * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "WedgeJulia"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `angle`, `count`, `hole` and `swirl`.
+#[derive(Debug)]
+pub struct WedgeSph(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for WedgeSph {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let WedgeSph(angle, count, hole, swirl) = *self;
+        let r = 1.0 / (length(point) + EPS);
+        let a = wedge(angle_yx(point) + swirl * r, angle, count);
+        Point::from_xy((r + hole) * a.cos(), (r + hole) * a.sin())
+    }
+
+    fn name(&self) -> &str {
+        "WedgeSph"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `x_frequency`, `x_scale`, `y_frequency` and `y_scale`.
+#[derive(Debug)]
+pub struct Waves2(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Waves2 {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Waves2(x_frequency, x_scale, y_frequency, y_scale) = *self;
+        Point::from_xy(
+            point.x + x_scale * (point.y * x_frequency).sin(),
+            point.y + y_scale * (point.x * y_frequency).sin()
+        )
+    }
+
+    fn name(&self) -> &str {
+        "Waves2"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+#[derive(Debug)]
+pub struct Exp;
+
+impl Variation for Exp {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let r = point.x.exp();
+        Point::from_xy(r * point.y.cos(), r * point.y.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Exp"
+    }
+}
+
+#[derive(Debug)]
+pub struct Log;
+
+impl Variation for Log {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(0.5 * sum_sq(point).ln(), angle_yx(point))
+    }
+
+    fn name(&self) -> &str {
+        "Log"
+    }
+}
+
+#[derive(Debug)]
+pub struct Sin;
+
+impl Variation for Sin {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.sin() * point.y.cosh(), point.x.cos() * point.y.sinh())
+    }
+
+    fn name(&self) -> &str {
+        "Sin"
+    }
+}
+
+#[derive(Debug)]
+pub struct Cos;
+
+impl Variation for Cos {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.cos() * point.y.cosh(), -point.x.sin() * point.y.sinh())
+    }
+
+    fn name(&self) -> &str {
+        "Cos"
+    }
+}
+
+#[derive(Debug)]
+pub struct Tan;
+
+impl Variation for Tan {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 1.0 / ((2.0 * point.x).cos() + (2.0 * point.y).cosh());
+        Point::from_xy((2.0 * point.x).sin() * d, (2.0 * point.y).sinh() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Tan"
+    }
+}
+
+#[derive(Debug)]
+pub struct Sec;
+
+impl Variation for Sec {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 2.0 / ((2.0 * point.x).cos() + (2.0 * point.y).cosh());
+        Point::from_xy(point.x.cos() * point.y.cosh() * d, point.x.sin() * point.y.sinh() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Sec"
+    }
+}
+
+#[derive(Debug)]
+pub struct Csc;
+
+impl Variation for Csc {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 2.0 / ((2.0 * point.y).cosh() - (2.0 * point.x).cos());
+        Point::from_xy(point.x.sin() * point.y.cosh() * d, -point.x.cos() * point.y.sinh() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Csc"
+    }
+}
+
+#[derive(Debug)]
+pub struct Cot;
+
+impl Variation for Cot {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 1.0 / ((2.0 * point.y).cosh() - (2.0 * point.x).cos());
+        Point::from_xy((2.0 * point.x).sin() * d, -(2.0 * point.y).sinh() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Cot"
+    }
+}
+
+#[derive(Debug)]
+pub struct Sinh;
+
+impl Variation for Sinh {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.sinh() * point.y.cos(), point.x.cosh() * point.y.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Sinh"
+    }
+}
+
+#[derive(Debug)]
+pub struct Cosh;
+
+impl Variation for Cosh {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        Point::from_xy(point.x.cosh() * point.y.cos(), point.x.sinh() * point.y.sin())
+    }
+
+    fn name(&self) -> &str {
+        "Cosh"
+    }
+}
+
+#[derive(Debug)]
+pub struct Tanh;
+
+impl Variation for Tanh {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 1.0 / ((2.0 * point.y).cos() + (2.0 * point.x).cosh());
+        Point::from_xy((2.0 * point.x).sinh() * d, (2.0 * point.y).sin() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Tanh"
+    }
+}
+
+#[derive(Debug)]
+pub struct Sech;
+
+impl Variation for Sech {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 2.0 / ((2.0 * point.y).cos() + (2.0 * point.x).cosh());
+        Point::from_xy(point.y.cos() * point.x.cosh() * d, -point.y.sin() * point.x.sinh() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Sech"
+    }
+}
+
+#[derive(Debug)]
+pub struct Csch;
+
+impl Variation for Csch {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 2.0 / ((2.0 * point.x).cosh() - (2.0 * point.y).cos());
+        Point::from_xy(point.x.sinh() * point.y.cos() * d, -point.x.cosh() * point.y.sin() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Csch"
+    }
+}
+
+#[derive(Debug)]
+pub struct Coth;
+
+impl Variation for Coth {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let d = 1.0 / ((2.0 * point.x).cosh() - (2.0 * point.y).cos());
+        Point::from_xy((2.0 * point.x).sinh() * d, (2.0 * point.y).sin() * d)
+    }
+
+    fn name(&self) -> &str {
+        "Coth"
+    }
+}
+
+/// Takes `frequency`, `amount`, `scale` and `symmetry`.
+#[derive(Debug)]
+pub struct Auger(pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Auger {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Auger(frequency, amount, scale, symmetry) = *self;
+        let s = (frequency * point.x).sin();
+        let t = (frequency * point.y).sin();
+        let dx = point.x + amount * (scale * t / 2.0 + point.x.abs() * t);
+        let dy = point.y + amount * (scale * s / 2.0 + point.y.abs() * s);
+        Point::from_xy(point.x + symmetry * (dx - point.x), dy)
+    }
+
+    fn name(&self) -> &str {
+        "Auger"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
+}
+
+/// Takes `re_a`, `im_a`, `re_b`, `im_b`, `re_c`, `im_c`, `re_d` and `im_d`.
+#[derive(Debug)]
+pub struct Mobius(pub Number, pub Number, pub Number, pub Number, pub Number, pub Number, pub Number, pub Number);
+
+impl Variation for Mobius {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
+        let Mobius(re_a, im_a, re_b, im_b, re_c, im_c, re_d, im_d) = *self;
+        let re_u = re_a * point.x - im_a * point.y + re_b;
+        let im_u = re_a * point.y + im_a * point.x + im_b;
+        let re_v = re_c * point.x - im_c * point.y + re_d;
+        let im_v = re_c * point.y + im_c * point.x + im_d;
+        let r = 1.0 / (re_v * re_v + im_v * im_v);
+        Point::from_xy((re_u * re_v + im_u * im_v) * r, (im_u * re_v - re_u * im_v) * r)
+    }
+
+    fn name(&self) -> &str {
+        "Mobius"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3, self.4, self.5, self.6, self.7]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Boarders;
+    use rand::{SeedableRng, XorShiftRng};
+    use types::{Point, Variation, AffineTransformation};
+
+    #[test]
+    fn boarders_moves_a_quarter_of_the_points_into_the_cells() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let identity = AffineTransformation::identity();
+        let point = Point::from_xy(0.3, 0.1);
+        let samples = 100000;
+
+        // (0.15, 0.05) inside the cell, (0.4, 0.0833...) on its border
+        let inside = (0..samples)
+            .map(|_| Boarders.apply(&point, &identity, &mut rng))
+            .filter(|result| result.x < 0.2)
+            .count();
+        let fraction = inside as f64 / samples as f64;
+
+        assert!((fraction - 0.25).abs() < 0.01, "{} of the points stayed inside the cell", fraction);
+    }
+}
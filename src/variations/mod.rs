@@ -1,65 +1,95 @@
 use types::{Point, Number, Variation, AffineTransformation};
+use rand::Rng;
 
-pub fn make_variation(name: &str, params: &[Number]) -> Result<Box<Variation>, &'static str> {
-    match name {
-        "Linear" => Ok(Box::new(Linear)),
-        "DeJong" => {
-            if params.len() < 4 { Err("Not enough parameters") }
-            else { Ok(Box::new(DeJong(params[0], params[1], params[2], params[3]))) }
-        },
-        "Trigonometric" => {
-            if params.len() < 8 { Err("Not enough parameters") }
-            else { Ok(Box::new(Trigonometric(params[0], params[1], params[2], params[3], params[4], params[5], params[6], params[7]))) }
-        },
-        "Unnamed" => {
-            if params.len() < 4 { Err("Not enough parameters") }
-            else { Ok(Box::new(Unnamed(params[0], params[1], params[2], params[3]))) }
-        }
-        _ => Err("Invalid variation")
-    }
+mod flam3;
+mod params;
+mod registry;
+pub use self::flam3::*;
+pub use self::params::ParamDescriptor;
+pub use self::registry::{VariationRegistry, register_variation};
+pub use self::registry::global as global_registry;
+
+/// Creates a variation known to the global registry from its parameters in
+/// the order of its descriptors, trailing ones can be left out to get their
+/// defaults.
+pub fn make_variation(name: &str, params: &[Number]) -> Result<Box<Variation>, String> {
+    make_variation_with_named_params(name, params, &[])
+}
+
+/// Like `make_variation`, with the named parameters overriding positional ones.
+pub fn make_variation_with_named_params(name: &str, params: &[Number], named_params: &[(&str, Number)]) -> Result<Box<Variation>, String> {
+    registry::global().read().unwrap().make(name, params, named_params)
 }
 
 #[derive(Debug)]
 pub struct Linear;
 
 impl Variation for Linear {
-    fn apply(&self, point: &Point, _: &AffineTransformation) -> Point {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
         Point::from_xy(point.x, point.y)
     }
+
+    fn name(&self) -> &str {
+        "Linear"
+    }
 }
 
 #[derive(Debug)]
 pub struct DeJong(pub Number, pub Number, pub Number, pub Number);
 
 impl Variation for DeJong {
-    fn apply(&self, point: &Point, _: &AffineTransformation) -> Point {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
         Point::from_xy(
             (self.0 * point.y).sin() - (self.1 * point.x).cos(),
             (self.2 * point.x).sin() - (self.3 * point.y).cos()
         )
     }
+
+    fn name(&self) -> &str {
+        "DeJong"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
 }
 
 #[derive(Debug)]
 pub struct Trigonometric(pub Number, pub Number, pub Number, pub Number, pub Number, pub Number, pub Number, pub Number);
 
 impl Variation for Trigonometric {
-    fn apply(&self, point: &Point, _: &AffineTransformation) -> Point {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
         Point::from_xy(
             self.0 * (self.1 * point.y).sin() + self.2 * (self.3 * point.x).cos(),
             self.4 * (self.5 * point.x).sin() + self.6 * (self.7 * point.y).cos()
         )
     }
+
+    fn name(&self) -> &str {
+        "Trigonometric"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3, self.4, self.5, self.6, self.7]
+    }
 }
 
 #[derive(Debug)]
 pub struct Unnamed(pub Number, pub Number, pub Number, pub Number);
 
 impl Variation for Unnamed {
-    fn apply(&self, point: &Point, _: &AffineTransformation) -> Point {
+    fn apply(&self, point: &Point, _: &AffineTransformation, _: &mut Rng) -> Point {
         Point::from_xy(
             point.y + self.0 * point.x.signum() * (self.1 * point.x - self.2).abs().sqrt(),
             self.3 - point.x
         )
     }
+
+    fn name(&self) -> &str {
+        "Unnamed"
+    }
+
+    fn params(&self) -> Vec<Number> {
+        vec![self.0, self.1, self.2, self.3]
+    }
 }
@@ -0,0 +1,338 @@
+use std::f64;
+use types::Number;
+
+/// Describes one of a variation's parameters.
+#[derive(Copy, Clone, Debug)]
+pub struct ParamDescriptor {
+    pub name: &'static str,
+    pub default: Number,
+    /// Smallest and largest allowed values.
+    pub range: (Number, Number),
+    /// Whether 0 is rejected too, for parameters that get divided by.
+    pub nonzero: bool
+}
+
+const ANY: (Number, Number) = (f64::NEG_INFINITY, f64::INFINITY);
+
+/// Parameters of every built-in variation, in the order they're passed by
+/// position.
+const PARAMS: &'static [(&'static str, &'static [ParamDescriptor])] = &[
+    ("Linear", &[]),
+    ("DeJong", &[
+        ParamDescriptor { name: "a", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "b", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "c", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "d", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Trigonometric", &[
+        ParamDescriptor { name: "a", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "b", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "c", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "d", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "e", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "f", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "g", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "h", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Unnamed", &[
+        ParamDescriptor { name: "a", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "b", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "c", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "d", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Sinusoidal", &[]),
+    ("Spherical", &[]),
+    ("Swirl", &[]),
+    ("Horseshoe", &[]),
+    ("Polar", &[]),
+    ("Handkerchief", &[]),
+    ("Heart", &[]),
+    ("Disc", &[]),
+    ("Spiral", &[]),
+    ("Hyperbolic", &[]),
+    ("Diamond", &[]),
+    ("Ex", &[]),
+    ("Julia", &[]),
+    ("Bent", &[]),
+    ("Waves", &[]),
+    ("Fisheye", &[]),
+    ("Popcorn", &[]),
+    ("Exponential", &[]),
+    ("Power", &[]),
+    ("Cosine", &[]),
+    ("Rings", &[]),
+    ("Fan", &[]),
+    ("Blob", &[
+        ParamDescriptor { name: "high", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "low", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "waves", default: 1.0, range: ANY, nonzero: false }
+    ]),
+    ("Pdj", &[
+        ParamDescriptor { name: "a", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "b", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "c", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "d", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Fan2", &[
+        ParamDescriptor { name: "x", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Rings2", &[
+        ParamDescriptor { name: "value", default: 1.0, range: ANY, nonzero: true }
+    ]),
+    ("Eyefish", &[]),
+    ("Bubble", &[]),
+    ("Cylinder", &[]),
+    ("Perspective", &[
+        ParamDescriptor { name: "angle", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "distance", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Noise", &[]),
+    ("Julian", &[
+        ParamDescriptor { name: "power", default: 1.0, range: ANY, nonzero: true },
+        ParamDescriptor { name: "distance", default: 1.0, range: ANY, nonzero: false }
+    ]),
+    ("Juliascope", &[
+        ParamDescriptor { name: "power", default: 1.0, range: ANY, nonzero: true },
+        ParamDescriptor { name: "distance", default: 1.0, range: ANY, nonzero: false }
+    ]),
+    ("Blur", &[]),
+    ("GaussianBlur", &[]),
+    ("RadialBlur", &[
+        ParamDescriptor { name: "angle", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Pie", &[
+        ParamDescriptor { name: "slices", default: 6.0, range: (1.0, f64::INFINITY), nonzero: false },
+        ParamDescriptor { name: "rotation", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "thickness", default: 0.5, range: (0.0, 1.0), nonzero: false }
+    ]),
+    ("Ngon", &[
+        ParamDescriptor { name: "sides", default: 5.0, range: (1.0, f64::INFINITY), nonzero: false },
+        ParamDescriptor { name: "power", default: 3.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "circle", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "corners", default: 2.0, range: ANY, nonzero: false }
+    ]),
+    ("Curl", &[
+        ParamDescriptor { name: "c1", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "c2", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Rectangles", &[
+        ParamDescriptor { name: "x", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y", default: 1.0, range: ANY, nonzero: false }
+    ]),
+    ("Tangent", &[]),
+    ("Square", &[]),
+    ("Cross", &[]),
+    ("Disc2", &[
+        ParamDescriptor { name: "rotation", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "twist", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("SuperShape", &[
+        ParamDescriptor { name: "rnd", default: 0.0, range: (0.0, 1.0), nonzero: false },
+        ParamDescriptor { name: "m", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "n1", default: 1.0, range: ANY, nonzero: true },
+        ParamDescriptor { name: "n2", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "n3", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "holes", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Flower", &[
+        ParamDescriptor { name: "petals", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "holes", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Conic", &[
+        ParamDescriptor { name: "eccentricity", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "holes", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Parabola", &[
+        ParamDescriptor { name: "height", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "width", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Bent2", &[
+        ParamDescriptor { name: "x", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y", default: 1.0, range: ANY, nonzero: false }
+    ]),
+    ("Bipolar", &[
+        ParamDescriptor { name: "shift", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Boarders", &[]),
+    ("Butterfly", &[]),
+    ("Cell", &[
+        ParamDescriptor { name: "size", default: 1.0, range: ANY, nonzero: true }
+    ]),
+    ("Cpow", &[
+        ParamDescriptor { name: "real", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "imaginary", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "power", default: 1.0, range: ANY, nonzero: true }
+    ]),
+    ("Curve", &[
+        ParamDescriptor { name: "x_amplitude", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y_amplitude", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "x_length", default: 1.0, range: ANY, nonzero: true },
+        ParamDescriptor { name: "y_length", default: 1.0, range: ANY, nonzero: true }
+    ]),
+    ("Edisc", &[]),
+    ("Elliptic", &[]),
+    ("Escher", &[
+        ParamDescriptor { name: "beta", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Foci", &[]),
+    ("Modulus", &[
+        ParamDescriptor { name: "x", default: 1.0, range: ANY, nonzero: true },
+        ParamDescriptor { name: "y", default: 1.0, range: ANY, nonzero: true }
+    ]),
+    ("Oscilloscope", &[
+        ParamDescriptor { name: "separation", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "frequency", default: f64::consts::PI, range: ANY, nonzero: false },
+        ParamDescriptor { name: "amplitude", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "damping", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Polar2", &[]),
+    ("Popcorn2", &[
+        ParamDescriptor { name: "x", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "c", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Separation", &[
+        ParamDescriptor { name: "x", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "x_inside", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y_inside", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Split", &[
+        ParamDescriptor { name: "x_size", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y_size", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Splits", &[
+        ParamDescriptor { name: "x", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Stripes", &[
+        ParamDescriptor { name: "space", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "warp", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Wedge", &[
+        ParamDescriptor { name: "angle", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "hole", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "count", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "swirl", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("WedgeJulia", &[
+        ParamDescriptor { name: "angle", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "count", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "power", default: 1.0, range: ANY, nonzero: true },
+        ParamDescriptor { name: "distance", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("WedgeSph", &[
+        ParamDescriptor { name: "angle", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "count", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "hole", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "swirl", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Waves2", &[
+        ParamDescriptor { name: "x_frequency", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "x_scale", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y_frequency", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "y_scale", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Exp", &[]),
+    ("Log", &[]),
+    ("Sin", &[]),
+    ("Cos", &[]),
+    ("Tan", &[]),
+    ("Sec", &[]),
+    ("Csc", &[]),
+    ("Cot", &[]),
+    ("Sinh", &[]),
+    ("Cosh", &[]),
+    ("Tanh", &[]),
+    ("Sech", &[]),
+    ("Csch", &[]),
+    ("Coth", &[]),
+    ("Auger", &[
+        ParamDescriptor { name: "frequency", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "amount", default: 0.5, range: ANY, nonzero: false },
+        ParamDescriptor { name: "scale", default: 1.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "symmetry", default: 0.0, range: ANY, nonzero: false }
+    ]),
+    ("Mobius", &[
+        ParamDescriptor { name: "re_a", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "im_a", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "re_b", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "im_b", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "re_c", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "im_c", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "re_d", default: 0.0, range: ANY, nonzero: false },
+        ParamDescriptor { name: "im_d", default: 0.0, range: ANY, nonzero: false }
+    ])
+];
+
+pub fn builtin_descriptors(name: &str) -> Option<&'static [ParamDescriptor]> {
+    PARAMS.iter().find(|&&(variation, _)| variation == name).map(|&(_, descriptors)| descriptors)
+}
+
+/// Combines positional parameters and named ones overriding them into a full
+/// positional list, taking the defaults for parameters given neither way.
+pub fn resolve_params(name: &str, descriptors: &[ParamDescriptor], params: &[Number], named_params: &[(&str, Number)]) -> Result<Vec<Number>, String> {
+    if params.len() > descriptors.len() {
+        return Err(format!("Variation {} takes {} parameters, got {}", name, descriptors.len(), params.len()));
+    }
+
+    let mut values: Vec<Number> = descriptors.iter().map(|descriptor| descriptor.default).collect();
+    for (i, param) in params.iter().enumerate() {
+        values[i] = *param;
+    }
+
+    for &(param_name, value) in named_params.iter() {
+        match descriptors.iter().position(|descriptor| descriptor.name == param_name) {
+            Some(i) => values[i] = value,
+            None => return Err(format!("Variation {} has no parameter named {}", name, param_name))
+        }
+    }
+
+    for (descriptor, value) in descriptors.iter().zip(values.iter()) {
+        let (min, max) = descriptor.range;
+
+        if !(*value >= min && *value <= max) {
+            return Err(format!("Parameter {} of variation {} must be between {} and {}, got {}", descriptor.name, name, min, max, value));
+        }
+
+        if *value == 0.0 && descriptor.nonzero {
+            return Err(format!("Parameter {} of variation {} can't be 0", descriptor.name, name));
+        }
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PARAMS, resolve_params};
+
+    #[test]
+    fn divisors_reject_zero() {
+        let mut divisors = Vec::new();
+
+        for &(name, descriptors) in PARAMS.iter() {
+            for descriptor in descriptors.iter().filter(|descriptor| descriptor.nonzero) {
+                divisors.push((name, descriptor.name));
+
+                assert!(resolve_params(name, descriptors, &[], &[(descriptor.name, 0.0)]).is_err(), "{} {}", name, descriptor.name);
+                assert!(resolve_params(name, descriptors, &[], &[(descriptor.name, 2.0)]).is_ok(), "{} {}", name, descriptor.name);
+            }
+        }
+
+        assert_eq!(divisors, vec![
+            ("Rings2", "value"),
+            ("Julian", "power"),
+            ("Juliascope", "power"),
+            ("SuperShape", "n1"),
+            ("Cell", "size"),
+            ("Cpow", "power"),
+            ("Curve", "x_length"),
+            ("Curve", "y_length"),
+            ("Modulus", "x"),
+            ("Modulus", "y"),
+            ("WedgeJulia", "power")
+        ]);
+    }
+}
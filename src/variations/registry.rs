@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::{Once, ONCE_INIT, RwLock};
+use types::{Number, Variation};
+use variations::*;
+use variations::params::{ParamDescriptor, builtin_descriptors, resolve_params};
+
+type Constructor = Box<Fn(&[Number]) -> Box<Variation> + Send + Sync>;
+
+struct Entry {
+    params: Vec<ParamDescriptor>,
+    constructor: Constructor
+}
+
+/// Maps variation names to constructors, so variations can be created by the
+/// names they're known by in messages.
+pub struct VariationRegistry {
+    variations: HashMap<String, Entry>
+}
+
+impl VariationRegistry {
+    /// A registry with every built-in variation.
+    pub fn new() -> VariationRegistry {
+        let mut registry = VariationRegistry::empty();
+
+        registry.register_builtin("Linear", |_| Box::new(Linear));
+        registry.register_builtin("DeJong", |params| Box::new(DeJong(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Trigonometric", |params| Box::new(Trigonometric(params[0], params[1], params[2], params[3], params[4], params[5], params[6], params[7])));
+        registry.register_builtin("Unnamed", |params| Box::new(Unnamed(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Sinusoidal", |_| Box::new(Sinusoidal));
+        registry.register_builtin("Spherical", |_| Box::new(Spherical));
+        registry.register_builtin("Swirl", |_| Box::new(Swirl));
+        registry.register_builtin("Horseshoe", |_| Box::new(Horseshoe));
+        registry.register_builtin("Polar", |_| Box::new(Polar));
+        registry.register_builtin("Handkerchief", |_| Box::new(Handkerchief));
+        registry.register_builtin("Heart", |_| Box::new(Heart));
+        registry.register_builtin("Disc", |_| Box::new(Disc));
+        registry.register_builtin("Spiral", |_| Box::new(Spiral));
+        registry.register_builtin("Hyperbolic", |_| Box::new(Hyperbolic));
+        registry.register_builtin("Diamond", |_| Box::new(Diamond));
+        registry.register_builtin("Ex", |_| Box::new(Ex));
+        registry.register_builtin("Julia", |_| Box::new(Julia));
+        registry.register_builtin("Bent", |_| Box::new(Bent));
+        registry.register_builtin("Waves", |_| Box::new(Waves));
+        registry.register_builtin("Fisheye", |_| Box::new(Fisheye));
+        registry.register_builtin("Popcorn", |_| Box::new(Popcorn));
+        registry.register_builtin("Exponential", |_| Box::new(Exponential));
+        registry.register_builtin("Power", |_| Box::new(Power));
+        registry.register_builtin("Cosine", |_| Box::new(Cosine));
+        registry.register_builtin("Rings", |_| Box::new(Rings));
+        registry.register_builtin("Fan", |_| Box::new(Fan));
+        registry.register_builtin("Blob", |params| Box::new(Blob(params[0], params[1], params[2])));
+        registry.register_builtin("Pdj", |params| Box::new(Pdj(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Fan2", |params| Box::new(Fan2(params[0], params[1])));
+        registry.register_builtin("Rings2", |params| Box::new(Rings2(params[0])));
+        registry.register_builtin("Eyefish", |_| Box::new(Eyefish));
+        registry.register_builtin("Bubble", |_| Box::new(Bubble));
+        registry.register_builtin("Cylinder", |_| Box::new(Cylinder));
+        registry.register_builtin("Perspective", |params| Box::new(Perspective(params[0], params[1])));
+        registry.register_builtin("Noise", |_| Box::new(Noise));
+        registry.register_builtin("Julian", |params| Box::new(Julian(params[0], params[1])));
+        registry.register_builtin("Juliascope", |params| Box::new(Juliascope(params[0], params[1])));
+        registry.register_builtin("Blur", |_| Box::new(Blur));
+        registry.register_builtin("GaussianBlur", |_| Box::new(GaussianBlur));
+        registry.register_builtin("RadialBlur", |params| Box::new(RadialBlur(params[0])));
+        registry.register_builtin("Pie", |params| Box::new(Pie(params[0], params[1], params[2])));
+        registry.register_builtin("Ngon", |params| Box::new(Ngon(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Curl", |params| Box::new(Curl(params[0], params[1])));
+        registry.register_builtin("Rectangles", |params| Box::new(Rectangles(params[0], params[1])));
+        registry.register_builtin("Tangent", |_| Box::new(Tangent));
+        registry.register_builtin("Square", |_| Box::new(Square));
+        registry.register_builtin("Cross", |_| Box::new(Cross));
+        registry.register_builtin("Disc2", |params| Box::new(Disc2(params[0], params[1])));
+        registry.register_builtin("SuperShape", |params| Box::new(SuperShape(params[0], params[1], params[2], params[3], params[4], params[5])));
+        registry.register_builtin("Flower", |params| Box::new(Flower(params[0], params[1])));
+        registry.register_builtin("Conic", |params| Box::new(Conic(params[0], params[1])));
+        registry.register_builtin("Parabola", |params| Box::new(Parabola(params[0], params[1])));
+        registry.register_builtin("Bent2", |params| Box::new(Bent2(params[0], params[1])));
+        registry.register_builtin("Bipolar", |params| Box::new(Bipolar(params[0])));
+        registry.register_builtin("Boarders", |_| Box::new(Boarders));
+        registry.register_builtin("Butterfly", |_| Box::new(Butterfly));
+        registry.register_builtin("Cell", |params| Box::new(Cell(params[0])));
+        registry.register_builtin("Cpow", |params| Box::new(Cpow(params[0], params[1], params[2])));
+        registry.register_builtin("Curve", |params| Box::new(Curve(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Edisc", |_| Box::new(Edisc));
+        registry.register_builtin("Elliptic", |_| Box::new(Elliptic));
+        registry.register_builtin("Escher", |params| Box::new(Escher(params[0])));
+        registry.register_builtin("Foci", |_| Box::new(Foci));
+        registry.register_builtin("Modulus", |params| Box::new(Modulus(params[0], params[1])));
+        registry.register_builtin("Oscilloscope", |params| Box::new(Oscilloscope(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Polar2", |_| Box::new(Polar2));
+        registry.register_builtin("Popcorn2", |params| Box::new(Popcorn2(params[0], params[1], params[2])));
+        registry.register_builtin("Separation", |params| Box::new(Separation(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Split", |params| Box::new(Split(params[0], params[1])));
+        registry.register_builtin("Splits", |params| Box::new(Splits(params[0], params[1])));
+        registry.register_builtin("Stripes", |params| Box::new(Stripes(params[0], params[1])));
+        registry.register_builtin("Wedge", |params| Box::new(Wedge(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("WedgeJulia", |params| Box::new(WedgeJulia(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("WedgeSph", |params| Box::new(WedgeSph(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Waves2", |params| Box::new(Waves2(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Exp", |_| Box::new(Exp));
+        registry.register_builtin("Log", |_| Box::new(Log));
+        registry.register_builtin("Sin", |_| Box::new(Sin));
+        registry.register_builtin("Cos", |_| Box::new(Cos));
+        registry.register_builtin("Tan", |_| Box::new(Tan));
+        registry.register_builtin("Sec", |_| Box::new(Sec));
+        registry.register_builtin("Csc", |_| Box::new(Csc));
+        registry.register_builtin("Cot", |_| Box::new(Cot));
+        registry.register_builtin("Sinh", |_| Box::new(Sinh));
+        registry.register_builtin("Cosh", |_| Box::new(Cosh));
+        registry.register_builtin("Tanh", |_| Box::new(Tanh));
+        registry.register_builtin("Sech", |_| Box::new(Sech));
+        registry.register_builtin("Csch", |_| Box::new(Csch));
+        registry.register_builtin("Coth", |_| Box::new(Coth));
+        registry.register_builtin("Auger", |params| Box::new(Auger(params[0], params[1], params[2], params[3])));
+        registry.register_builtin("Mobius", |params| Box::new(Mobius(params[0], params[1], params[2], params[3], params[4], params[5], params[6], params[7])));
+
+        registry
+    }
+
+    pub fn empty() -> VariationRegistry {
+        VariationRegistry { variations: HashMap::new() }
+    }
+
+    /// Adds a variation, replacing any registered under the same name. The
+    /// constructor always gets one value per descriptor, in the same order.
+    pub fn register<F>(&mut self, name: &str, params: &[ParamDescriptor], constructor: F)
+        where F: Fn(&[Number]) -> Box<Variation> + Send + Sync + 'static
+    {
+        self.variations.insert(name.to_string(), Entry {
+            params: params.to_vec(),
+            constructor: Box::new(constructor)
+        });
+    }
+
+    fn register_builtin<F>(&mut self, name: &str, constructor: F)
+        where F: Fn(&[Number]) -> Box<Variation> + Send + Sync + 'static
+    {
+        let params = builtin_descriptors(name).expect("Built-in variation without parameter descriptors");
+        self.register(name, params, constructor);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.variations.contains_key(name)
+    }
+
+    /// Descriptors of the parameters of the named variation, or `None` if it
+    /// isn't registered.
+    pub fn param_descriptors(&self, name: &str) -> Option<&[ParamDescriptor]> {
+        self.variations.get(name).map(|entry| &entry.params[..])
+    }
+
+    /// Creates a variation from positional parameters and named ones
+    /// overriding them, taking the defaults for those given neither way.
+    pub fn make(&self, name: &str, params: &[Number], named_params: &[(&str, Number)]) -> Result<Box<Variation>, String> {
+        let entry = try!(self.variations.get(name).ok_or_else(|| format!("Unknown variation {}", name)));
+        let params = try!(resolve_params(name, &entry.params, params, named_params));
+
+        Ok((entry.constructor)(&params))
+    }
+}
+
+static INIT: Once = ONCE_INIT;
+static mut GLOBAL: *const RwLock<VariationRegistry> = 0 as *const RwLock<VariationRegistry>;
+
+/// The registry used when reading messages, starts out with the built-ins.
+pub fn global() -> &'static RwLock<VariationRegistry> {
+    unsafe {
+        INIT.call_once(|| {
+            GLOBAL = Box::into_raw(Box::new(RwLock::new(VariationRegistry::new())));
+        });
+
+        &*GLOBAL
+    }
+}
+
+/// Adds a variation to the global registry, making it available to every
+/// message read afterwards. See `VariationRegistry::register`.
+pub fn register_variation<F>(name: &str, params: &[ParamDescriptor], constructor: F)
+    where F: Fn(&[Number]) -> Box<Variation> + Send + Sync + 'static
+{
+    global().write().unwrap().register(name, params, constructor);
+}